@@ -4,10 +4,26 @@ use pnetlink::packet::netlink::NetlinkConnection;
 use pnetlink::packet::route::link::{Links,Link};
 use pnetlink::packet::route::addr::{Addresses,Addr};
 
+fn debug_or_empty<T: ::std::fmt::Debug>(prefix: &str, val: Option<T>) -> String {
+    match val {
+        Some(val) => format!("{}{:?} ", prefix, val),
+        None => " ".to_owned(),
+    }
+}
+
+fn display_or_empty<T: ::std::fmt::Display>(prefix: &str, val: Option<T>) -> String {
+    match val {
+        Some(val) => format!("{}{} ", prefix, val),
+        None => " ".to_owned(),
+    }
+}
+
 fn print_link(link: &Link) {
-    println!("{}: {}: <{:?}> mtu {:?} qdisc {} state {:?}", link.get_index(), link.get_name().unwrap(),
-                link.get_flags(), link.get_mtu().unwrap(), link.get_qdisc().unwrap(), link.get_state());
-    println!("   Link/{:?} {:?} brd {:?}", link.get_type(), link.get_hw_addr().unwrap(), link.get_broadcast().unwrap());
+    let mtu = display_or_empty("mtu ", link.get_mtu().unwrap());
+    let qdisc = display_or_empty("qdisc ", link.get_qdisc().unwrap());
+    println!("{}: {}: <{:?}> {}{}state {:?}", link.get_index(), link.get_name().unwrap().unwrap_or_default(),
+                link.get_flags(), mtu, qdisc, link.get_state().unwrap());
+    println!("   Link/{:?} {:?} brd {:?}", link.get_type(), link.get_hw_addr(), link.get_broadcast());
 }
 
 fn print_addr(addr: &Addr) {
@@ -16,31 +32,20 @@ fn print_addr(addr: &Addr) {
         10 => "inet6",
         _ => "unknown",
     };
-    fn debug_or_empty<T: ::std::fmt::Debug>(prefix: &str, val: Option<T>) -> String {
-        match val {
-            Some(val) => format!("{}{:?} ", prefix, val),
-            None => " ".to_owned(),
-        }
-    }
-    fn display_or_empty<T: ::std::fmt::Display>(prefix: &str, val: Option<T>) -> String {
-        match val {
-            Some(val) => format!("{}{} ", prefix, val),
-            None => " ".to_owned(),
-        }
-    }
-    let broadcast = debug_or_empty("brd ", addr.get_broadcast_ip());
-    let label = display_or_empty("", addr.get_label());
-    println!("   {} {:?}/{}{}scope {:?} {}", family, addr.get_ip().unwrap(), addr.get_prefix_len(), broadcast, addr.get_scope(), label);
+    let ip = debug_or_empty("", addr.get_ip().unwrap());
+    let broadcast = debug_or_empty("brd ", addr.get_broadcast_ip().unwrap());
+    let label = display_or_empty("", addr.get_label().unwrap());
+    println!("   {} {}/{}{}scope {:?} {}", family, ip.trim_end(), addr.get_prefix_len(), broadcast, addr.get_scope(), label);
 }
 
 fn main() {
-    let mut conn = NetlinkConnection::new();
-    let links = conn.iter_links().unwrap().collect::<Vec<_>>();
+    let mut conn = NetlinkConnection::new().unwrap();
+    let links = conn.iter_links().unwrap().collect::<Result<Vec<_>,_>>().unwrap();
     for link in links {
         print_link(&link);
         for addr in conn.get_link_addrs(None, &link).unwrap() {
             //println!("{:?}", addr.get_ip());
-            print_addr(&addr);
+            print_addr(&addr.unwrap());
         }
     }
 }
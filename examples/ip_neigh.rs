@@ -7,20 +7,31 @@ use pnetlink::packet::route::link::{Links, Link};
 use pnetlink::packet::route::neighbour::{Neighbour, Neighbours, NOARP};
 
 fn main() {
-    let mut conn = NetlinkConnection::new();
-    let links =
-        conn.iter_links().unwrap().map(|link| (link.get_index(), link)).collect::<HashMap<_, _>>();
-    let neighbours = conn.iter_neighbours(None).unwrap().collect::<Vec<_>>();
+    let mut conn = NetlinkConnection::new().unwrap();
+    let links = conn.iter_links()
+        .unwrap()
+        .map(|link| link.map(|link| (link.get_index(), link)))
+        .collect::<Result<HashMap<_, _>, _>>()
+        .unwrap();
+    let neighbours = conn.iter_neighbours(None).unwrap().collect::<Result<Vec<_>, _>>().unwrap();
     for neighbour in neighbours {
         if neighbour.get_state() == NOARP {
             continue;
         }
+        // bridge FDB entries have no destination
+        let destination = match neighbour.get_destination().unwrap() {
+            Some(destination) => destination,
+            None => continue,
+        };
         let ifindex = neighbour.get_ifindex();
-        let link = links.get(&ifindex).unwrap();
+        let name = match links.get(&ifindex).map(|link| link.get_name().unwrap()) {
+            Some(Some(name)) => name,
+            _ => format!("if{}", ifindex),
+        };
         println!("{:?} dev {} lladdr {:?} {:?}",
-                 neighbour.get_destination().unwrap(),
-                 link.get_name().unwrap(),
-                 neighbour.get_ll_addr().unwrap(),
+                 destination,
+                 name,
+                 neighbour.get_ll_addr(),
                  neighbour.get_state());
     }
 }
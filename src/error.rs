@@ -0,0 +1,262 @@
+//! Error type shared by all Netlink operations
+use packet::netlink::{NetlinkPacket,NetlinkErrorPacket};
+use packet::netlink::{NLM_F_CAPPED,NLM_F_ACK_TLVS};
+use packet::netlink::{NLMSGERR_ATTR_MSG,NLMSGERR_ATTR_OFFS,NLMSGERR_ATTR_COOKIE};
+use packet::route::RtAttrIterator;
+use pnet::packet::Packet;
+use byteorder::{ByteOrder,NativeEndian};
+use std::cmp;
+use std::error;
+use std::fmt;
+use std::io;
+use std::result;
+
+/// Netlink operation error
+#[derive(Debug)]
+pub enum Error {
+    /// Socket level error
+    Io(io::Error),
+    /// Kernel rejected a request, see `KernelError`
+    Kernel(KernelError),
+    /// Message is shorter than its headers claim
+    Truncated,
+    /// Message or attribute contents are not what was expected
+    Malformed(&'static str),
+    /// Address family is not supported
+    UnsupportedFamily(u8),
+    /// Kernel reported NLMSG_OVERRUN, some messages were lost
+    Overrun,
+    /// Dumped objects changed while the dump was in progress (NLM_F_DUMP_INTR),
+    /// the result may be inconsistent
+    DumpInterrupted,
+    /// Datagram of `len` bytes does not fit into `capacity` bytes buffer
+    DatagramTruncated {
+        len: usize,
+        capacity: usize,
+    },
+    /// Datagram of `len` bytes was received into `capacity` bytes buffer
+    /// and the part that did not fit is lost, unlike with
+    /// `DatagramTruncated` it can not be read again
+    DatagramLost {
+        len: usize,
+        capacity: usize,
+    },
+    /// Link was created but is not there anymore when looked up by name,
+    /// e.g. it was deleted or renamed concurrently
+    LinkVanished(String),
+}
+
+/// Result type for Netlink operations
+pub type Result<T> = result::Result<T, Error>;
+
+/// Error reply (NLMSG_ERROR with non-zero code) sent by the kernel
+///
+/// With extended ACKs enabled (see `NetlinkSocket::enable_ext_ack`)
+/// the kernel may explain the error and point at the offending attribute.
+#[derive(Debug)]
+pub struct KernelError {
+    errno: i32,
+    request: Vec<u8>,
+    message: Option<String>,
+    offset: Option<u32>,
+    cookie: Option<Vec<u8>>,
+}
+
+impl KernelError {
+    /// Creates an error from positive `errno` and the echoed
+    /// request message `request`
+    pub fn new(errno: i32, request: &[u8]) -> Self {
+        KernelError {
+            errno: errno,
+            request: request.to_owned(),
+            message: None,
+            offset: None,
+            cookie: None,
+        }
+    }
+
+    /// Parses NLMSG_ERROR message `reply` including extended ACK attributes,
+    /// `None` if the reply is too short to hold an error code
+    pub fn from_reply(reply: &NetlinkPacket) -> Option<Self> {
+        let err = match NetlinkErrorPacket::new(reply.payload()) {
+            Some(err) => err,
+            None => return None,
+        };
+        let errno = -(err.get_error() as i32);
+        let payload = err.payload();
+        // the request is echoed in full unless the kernel capped it
+        // to the header, extended ACK attributes follow it
+        let request_len = match NetlinkPacket::new(payload) {
+            Some(_) if reply.get_flags().contains(NLM_F_CAPPED) => NetlinkPacket::minimum_packet_size(),
+            Some(ref request) => ::util::align(request.get_length() as usize),
+            None => payload.len(),
+        };
+        let request_len = cmp::min(request_len, payload.len());
+        let mut error = KernelError::new(errno, &payload[..request_len]);
+        if !reply.get_flags().contains(NLM_F_ACK_TLVS) {
+            return Some(error);
+        }
+        for rta in RtAttrIterator::new(&payload[request_len..]) {
+            let value = rta.payload();
+            match rta.get_rta_type() {
+                NLMSGERR_ATTR_MSG => {
+                    let msg = value.split(|b| *b == 0).next().unwrap_or(value);
+                    error.message = Some(String::from_utf8_lossy(msg).into_owned());
+                },
+                NLMSGERR_ATTR_OFFS if value.len() == 4 => {
+                    error.offset = Some(NativeEndian::read_u32(value));
+                },
+                NLMSGERR_ATTR_COOKIE => {
+                    error.cookie = Some(value.to_owned());
+                },
+                _ => {},
+            }
+        }
+        Some(error)
+    }
+
+    /// Positive errno value
+    pub fn errno(&self) -> i32 {
+        self.errno
+    }
+
+    /// Request which caused the error, as echoed by the kernel
+    ///
+    /// Only the header is available when the reply was capped.
+    pub fn request(&self) -> Option<NetlinkPacket> {
+        NetlinkPacket::new(&self.request)
+    }
+
+    /// Human-readable explanation from the kernel (extended ACK)
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_ref().map(|msg| &msg[..])
+    }
+
+    /// Offset of the offending attribute from the start
+    /// of the request message (extended ACK)
+    pub fn offset(&self) -> Option<u32> {
+        self.offset
+    }
+
+    /// Opaque cookie attached by the kernel (extended ACK)
+    pub fn cookie(&self) -> Option<&[u8]> {
+        self.cookie.as_ref().map(|cookie| &cookie[..])
+    }
+}
+
+impl fmt::Display for KernelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "{}", io::Error::from_raw_os_error(self.errno)));
+        if let Some(ref msg) = self.message {
+            try!(write!(f, ": {}", msg));
+        }
+        if let Some(req) = self.request() {
+            try!(write!(f, " (request type {} seq {}", req.get_kind(), req.get_seq()));
+            if let Some(offset) = self.offset {
+                try!(write!(f, ", attribute at offset {}", offset));
+            }
+            try!(write!(f, ")"));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref e) => write!(f, "netlink socket error: {}", e),
+            Error::Kernel(ref e) => write!(f, "kernel error: {}", e),
+            Error::Truncated => write!(f, "truncated netlink message"),
+            Error::Malformed(what) => write!(f, "malformed netlink message: {}", what),
+            Error::UnsupportedFamily(family) => write!(f, "unsupported address family {}", family),
+            Error::Overrun => write!(f, "netlink overrun, messages were lost"),
+            Error::DumpInterrupted => write!(f, "netlink dump was interrupted by a concurrent change"),
+            Error::DatagramTruncated { len, capacity } =>
+                write!(f, "{} bytes datagram does not fit into {} bytes buffer", len, capacity),
+            Error::DatagramLost { len, capacity } =>
+                write!(f, "{} bytes datagram was cut to {} bytes and lost", len, capacity),
+            Error::LinkVanished(ref name) => write!(f, "link {} vanished after creation", name),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::Io(ref e) => e.description(),
+            Error::Kernel(_) => "kernel error",
+            Error::Truncated => "truncated netlink message",
+            Error::Malformed(_) => "malformed netlink message",
+            Error::UnsupportedFamily(_) => "unsupported address family",
+            Error::Overrun => "netlink overrun",
+            Error::DumpInterrupted => "netlink dump interrupted",
+            Error::DatagramTruncated { .. } => "truncated netlink datagram",
+            Error::DatagramLost { .. } => "lost netlink datagram",
+            Error::LinkVanished(_) => "created link vanished",
+        }
+    }
+
+    fn cause(&self) -> Option<&error::Error> {
+        match *self {
+            Error::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    /// Unwraps `Error` passed through `Read` implementations
+    fn from(e: io::Error) -> Self {
+        // OS errors have no payload, `into_inner` would drop their code
+        if e.get_ref().is_none() {
+            return Error::Io(e);
+        }
+        let kind = e.kind();
+        match e.into_inner().map(|inner| inner.downcast::<Error>()) {
+            Some(Ok(inner)) => *inner,
+            Some(Err(inner)) => Error::Io(io::Error::new(kind, inner)),
+            None => Error::Io(io::Error::from(kind)),
+        }
+    }
+}
+
+impl From<Error> for io::Error {
+    /// Wraps `Error`, kernel errors keep the kind matching their errno
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Io(e) => e,
+            Error::Kernel(e) => {
+                let kind = io::Error::from_raw_os_error(e.errno).kind();
+                io::Error::new(kind, Error::Kernel(e))
+            },
+            e => io::Error::new(io::ErrorKind::InvalidData, e),
+        }
+    }
+}
+
+#[test]
+fn parse_ext_ack() {
+    let reply = vec![
+        56, 0, 0, 0, 2, 0, 0, 3, 1, 0, 0, 0, 0, 0, 0, 0, // header, CAPPED | ACK_TLVS
+        234, 255, 255, 255, // -EINVAL
+        48, 0, 0, 0, 16, 0, 5, 6, 1, 0, 0, 0, 0, 0, 0, 0, // capped request
+        10, 0, 1, 0, b'o', b'o', b'p', b's', 0, 0, 0, 0, // NLMSGERR_ATTR_MSG
+        8, 0, 2, 0, 32, 0, 0, 0, // NLMSGERR_ATTR_OFFS
+    ];
+    let reply = NetlinkPacket::new(&reply).unwrap();
+    let err = KernelError::from_reply(&reply).unwrap();
+    assert_eq!(err.errno(), 22);
+    assert_eq!(err.message(), Some("oops"));
+    assert_eq!(err.offset(), Some(32));
+    assert_eq!(err.request().unwrap().get_kind(), 16);
+}
+
+#[test]
+fn kernel_error_through_io() {
+    let err = io::Error::from(Error::Kernel(KernelError::new(::libc::ENOENT, &[])));
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    match Error::from(err) {
+        Error::Kernel(e) => assert_eq!(e.errno(), ::libc::ENOENT),
+        e => panic!("unexpected {:?}", e),
+    }
+}
@@ -3,13 +3,26 @@
 //!
 //! `socket` module can be used to establish Netlink socket
 //! `packet` contains high level functions and traits
+//! `error` defines `Error` returned by all of them
+//! `netns` allows opening sockets in other network namespaces
 #[macro_use]
 extern crate bitflags; 
 extern crate pnet;
+extern crate pnet_macros;
 extern crate pnet_macros_support;
 extern crate libc;
 extern crate byteorder;
+#[cfg(feature = "mio")]
+extern crate mio;
+#[cfg(feature = "async")]
+extern crate tokio;
+#[cfg(feature = "async")]
+extern crate futures;
 
+pub mod error;
 pub mod socket;
 pub mod packet;
+pub mod netns;
 mod util;
+
+pub use error::{Error,Result};
@@ -0,0 +1,255 @@
+//! Network namespaces
+//!
+//! Netlink sockets operate in the network namespace they were created in,
+//! so a socket opened inside another namespace keeps configuring it even
+//! when used from the caller's namespace.
+//!
+//! Named namespaces are managed the same way as `ip netns` does it,
+//! see `create`, `delete` and `list`.
+use libc;
+use std::ffi::CString;
+use std::fs::{self,File,OpenOptions};
+use std::io::{Error,ErrorKind,Result};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd,RawFd};
+use std::path::{Path,PathBuf};
+use std::ptr;
+use std::thread;
+
+/// Directory where `ip netns` keeps named namespaces
+pub const NETNS_RUN_DIR: &'static str = "/var/run/netns";
+
+/// Network namespace reference
+#[derive(Debug,Clone)]
+pub enum NetNs {
+    /// Namespace file, e.g. `/var/run/netns/<name>`
+    Path(PathBuf),
+    /// Namespace of process `pid`, i.e. `/proc/<pid>/ns/net`
+    Pid(u32),
+    /// Open namespace file descriptor, it is not closed by `NetNs`
+    Fd(RawFd),
+}
+
+impl NetNs {
+    /// Namespace created by `ip netns add <name>`
+    pub fn named(name: &str) -> Self {
+        NetNs::Path(Path::new(NETNS_RUN_DIR).join(name))
+    }
+
+    /// Namespace file path, `None` for `NetNs::Fd`
+    pub fn path(&self) -> Option<PathBuf> {
+        match *self {
+            NetNs::Path(ref path) => Some(path.clone()),
+            NetNs::Pid(pid) => Some(PathBuf::from(format!("/proc/{}/ns/net", pid))),
+            NetNs::Fd(_) => None,
+        }
+    }
+
+    /// Opens the namespace file, `None` for `NetNs::Fd`
+    pub fn open(&self) -> Result<Option<File>> {
+        match self.path() {
+            Some(path) => File::open(path).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Runs `f` on a helper thread switched into the namespace
+    ///
+    /// The calling thread's namespace is left untouched, the helper
+    /// thread exits as soon as `f` returns.
+    pub fn run<F, T>(&self, f: F) -> Result<T>
+        where F: FnOnce() -> Result<T> + Send + 'static, T: Send + 'static {
+        // keep the file open until setns is done
+        let open = |path: PathBuf| File::open(path).map(|file| (file.as_raw_fd(), Some(file)));
+        let (fd, file) = match *self {
+            NetNs::Path(ref path) => try!(open(path.clone())),
+            NetNs::Pid(pid) => try!(open(PathBuf::from(format!("/proc/{}/ns/net", pid)))),
+            NetNs::Fd(fd) => (fd, None),
+        };
+        let handle = thread::spawn(move || {
+            if unsafe { libc::setns(fd, libc::CLONE_NEWNET) } < 0 {
+                return Err(Error::last_os_error());
+            }
+            f()
+        });
+        let res = handle.join();
+        drop(file);
+        match res {
+            Ok(res) => res,
+            Err(_) => Err(Error::new(ErrorKind::Other, "netns helper thread panicked")),
+        }
+    }
+}
+
+/// Creates named namespace `name`, like `ip netns add`
+///
+/// A fresh namespace is unshared on a helper thread and kept alive
+/// by a bind mount of its file under `NETNS_RUN_DIR`.
+pub fn create(name: &str) -> Result<NetNs> {
+    try!(check_name(name));
+    try!(fs::create_dir_all(NETNS_RUN_DIR));
+    try!(make_run_dir_shared());
+    let netns = NetNs::named(name);
+    let path = netns.path().unwrap();
+    // mount point, fails if the namespace already exists
+    try!(OpenOptions::new().write(true).create_new(true).open(&path));
+    let target = try!(cstring(&path));
+    let res = thread::spawn(move || {
+        if unsafe { libc::unshare(libc::CLONE_NEWNET) } < 0 {
+            return Err(Error::last_os_error());
+        }
+        let tid = unsafe { libc::syscall(libc::SYS_gettid) };
+        let source = try!(cstring(Path::new(&format!("/proc/self/task/{}/ns/net", tid))));
+        mount(&source, &target, libc::MS_BIND)
+    }).join().unwrap_or_else(|_| Err(Error::new(ErrorKind::Other, "netns helper thread panicked")));
+    if let Err(e) = res {
+        let _ = fs::remove_file(&path);
+        return Err(e);
+    }
+    Ok(netns)
+}
+
+/// Deletes named namespace `name`, like `ip netns delete`
+///
+/// The namespace itself is destroyed once no process or socket uses it.
+pub fn delete(name: &str) -> Result<()> {
+    try!(check_name(name));
+    let path = NetNs::named(name).path().unwrap();
+    let target = try!(cstring(&path));
+    if unsafe { libc::umount2(target.as_ptr(), libc::MNT_DETACH) } < 0 {
+        let err = Error::last_os_error();
+        // not mounted, remove the leftover file anyway
+        if err.raw_os_error() != Some(libc::EINVAL) {
+            return Err(err);
+        }
+    }
+    fs::remove_file(&path)
+}
+
+/// Lists named namespaces, like `ip netns list`
+pub fn list() -> Result<Vec<String>> {
+    let entries = match fs::read_dir(NETNS_RUN_DIR) {
+        Ok(entries) => entries,
+        Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e),
+    };
+    let mut names = vec![];
+    for entry in entries {
+        let entry = try!(entry);
+        if let Ok(name) = entry.file_name().into_string() {
+            names.push(name);
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+fn check_name(name: &str) -> Result<()> {
+    if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+        return Err(Error::new(ErrorKind::InvalidInput, "invalid netns name"));
+    }
+    Ok(())
+}
+
+/// Makes mounts under `NETNS_RUN_DIR` propagate to other mount namespaces,
+/// the directory is bind mounted onto itself if it is not a mount point
+fn make_run_dir_shared() -> Result<()> {
+    let dir = try!(cstring(Path::new(NETNS_RUN_DIR)));
+    let none = CString::new("none").unwrap();
+    let empty = CString::new("").unwrap();
+    let shared = |dir: &CString| unsafe {
+        libc::mount(empty.as_ptr(), dir.as_ptr(), none.as_ptr(), libc::MS_SHARED | libc::MS_REC, ptr::null())
+    };
+    if shared(&dir) == 0 {
+        return Ok(());
+    }
+    let err = Error::last_os_error();
+    if err.raw_os_error() != Some(libc::EINVAL) {
+        return Err(err);
+    }
+    try!(mount(&dir, &dir, libc::MS_BIND | libc::MS_REC));
+    if shared(&dir) < 0 {
+        return Err(Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn mount(source: &CString, target: &CString, flags: libc::c_ulong) -> Result<()> {
+    let none = CString::new("none").unwrap();
+    let res = unsafe {
+        libc::mount(source.as_ptr(), target.as_ptr(), none.as_ptr(), flags, ptr::null())
+    };
+    if res < 0 {
+        return Err(Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn cstring(path: &Path) -> Result<CString> {
+    CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| Error::new(ErrorKind::InvalidInput, "path contains NUL"))
+}
+
+#[test]
+fn netns_paths() {
+    assert_eq!(NetNs::named("blue").path(), Some(PathBuf::from("/var/run/netns/blue")));
+    assert_eq!(NetNs::Pid(1).path(), Some(PathBuf::from("/proc/1/ns/net")));
+    assert_eq!(NetNs::Fd(3).path(), None);
+}
+
+#[test]
+fn run_in_netns() {
+    let own = NetNs::Pid(unsafe { libc::getpid() } as u32);
+    assert_eq!(own.run(|| Ok(42)).unwrap(), 42);
+    let err = own.run(|| -> Result<()> { Err(Error::new(ErrorKind::PermissionDenied, "inner")) }).unwrap_err();
+    assert_eq!(err.to_string(), "inner");
+    assert!(NetNs::Path(PathBuf::from("/nonexistent/netns")).run(|| Ok(())).is_err());
+    // not a namespace file, setns fails
+    let err = NetNs::Path(PathBuf::from("/proc/self/status")).run(|| Ok(())).unwrap_err();
+    assert_eq!(err.raw_os_error(), Some(libc::EINVAL));
+}
+
+#[test]
+fn bind_in_netns() {
+    use socket::{NetlinkSocket,NetlinkProtocol};
+
+    let own = NetNs::Pid(unsafe { libc::getpid() } as u32);
+    let sock = NetlinkSocket::bind_in_netns(NetlinkProtocol::Route, 0, &own).unwrap();
+    assert!(sock.port_id().unwrap() != 0);
+    let missing = NetNs::Path(PathBuf::from("/nonexistent/netns"));
+    assert!(NetlinkSocket::bind_in_netns(NetlinkProtocol::Route, 0, &missing).is_err());
+}
+
+#[test]
+fn check_names() {
+    for name in &["", ".", "..", "a/b", "/blue"] {
+        assert_eq!(check_name(name).unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+    assert!(check_name("blue").is_ok());
+    assert!(create("..").is_err());
+    assert!(delete("a/b").is_err());
+}
+
+#[test]
+fn create_and_delete_netns() {
+    use packet::netlink::NetlinkConnection;
+    use packet::route::nsid::{NetnsIds,NETNSA_NSID_NOT_ASSIGNED};
+
+    // mounting needs CAP_SYS_ADMIN
+    if unsafe { libc::geteuid() } != 0 {
+        return;
+    }
+    let name = format!("pnetlink{}", unsafe { libc::getpid() });
+    let netns = create(&name).unwrap();
+    assert!(list().unwrap().contains(&name));
+    assert!(create(&name).is_err());
+
+    let mut conn = NetlinkConnection::new().unwrap();
+    conn.set_netns_id(&netns, NETNSA_NSID_NOT_ASSIGNED).unwrap();
+    let id = conn.get_netns_id(&netns).unwrap().unwrap();
+    assert_eq!(conn.get_netns_name(id).unwrap(), Some(name.clone()));
+
+    delete(&name).unwrap();
+    assert!(!list().unwrap().contains(&name));
+    assert!(conn.get_netns_id(&netns).is_err());
+}
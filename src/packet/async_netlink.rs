@@ -0,0 +1,167 @@
+//! Asynchronous Netlink connection on top of tokio
+//!
+//! Enabled with the `async` cargo feature. `AsyncNetlinkConnection` is the
+//! counterpart of `NetlinkConnection`: requests resolve to a `Stream` of
+//! reply packets instead of a blocking `NetlinkReader`.
+//!
+//! # Example
+//! ```ignore
+//! use pnetlink::packet::async_netlink::AsyncNetlinkConnection;
+//! use pnetlink::packet::route::link::AsyncLinks;
+//!
+//! let mut conn = AsyncNetlinkConnection::new()?;
+//! let mut links = conn.iter_links().await?;
+//! while let Some(link) = links.try_next().await? {
+//!    ...
+//! }
+//! ```
+use packet::netlink::{NetlinkPacket,MutableNetlinkPacket,NetlinkReader};
+use socket::{NetlinkSocket,NetlinkProtocol};
+use error::{Error,Result};
+use netns::NetNs;
+use pnet::packet::Packet;
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context,Poll};
+
+use futures::future::{self,BoxFuture,Future,FutureExt,TryFutureExt};
+use futures::stream::{Stream,TryStreamExt};
+use tokio::io::unix::AsyncFd;
+
+/// AsyncNetlinkConnection represents active netlink connection
+/// driven by the tokio reactor
+pub struct AsyncNetlinkConnection {
+    sock: AsyncFd<NetlinkSocket>,
+    port_id: u32,
+    seq: u32,
+}
+
+impl AsyncNetlinkConnection {
+    /// Opens a non-blocking NETLINK_ROUTE socket and registers it
+    /// with the current tokio runtime
+    pub fn new() -> Result<Self> {
+        let sock = try!(NetlinkSocket::bind(NetlinkProtocol::Route, 0 as u32));
+        Self::from_socket(sock)
+    }
+
+    /// Same as `new` but the socket is opened inside network namespace `netns`
+    pub fn new_in_netns(netns: &NetNs) -> Result<Self> {
+        let sock = try!(NetlinkSocket::bind_in_netns(NetlinkProtocol::Route, 0 as u32, netns));
+        Self::from_socket(sock)
+    }
+
+    fn from_socket(mut sock: NetlinkSocket) -> Result<Self> {
+        try!(sock.set_nonblocking(true));
+        let _ = sock.enable_ext_ack();
+        let port_id = try!(sock.port_id());
+        Ok(AsyncNetlinkConnection {
+            sock: try!(AsyncFd::new(sock)),
+            port_id: port_id,
+            seq: 0,
+        })
+    }
+
+    /// Port id assigned to the underlying socket
+    pub fn port_id(&self) -> u32 {
+        self.port_id
+    }
+
+    /// Sequence number of the last request sent
+    pub fn seq(&self) -> u32 {
+        self.seq
+    }
+
+    /// Sends `msg`, the returned future resolves to a stream of replies
+    ///
+    /// The message is stamped with the next sequence number and
+    /// the connection's port id, the stream skips anything else.
+    pub fn send<'a>(&'a mut self, msg: NetlinkPacket<'static>) -> Request<'a> {
+        self.seq = self.seq.wrapping_add(1);
+        let mut buf = msg.packet().to_owned();
+        {
+            let mut pkt = MutableNetlinkPacket::new(&mut buf).unwrap();
+            pkt.set_seq(self.seq);
+            pkt.set_pid(self.port_id);
+        }
+        Request {
+            conn: Some(self),
+            msg: NetlinkPacket::owned(buf).unwrap(),
+        }
+    }
+
+    /// Sends `msg` and waits for the kernel to acknowledge it,
+    /// ignoring everything but errors
+    pub fn send_ack<'a>(&'a mut self, msg: NetlinkPacket<'static>) -> BoxFuture<'a, Result<()>> {
+        self.send(msg).and_then(|replies| {
+            replies.try_for_each(|_| future::ready(Ok(())))
+        }).boxed()
+    }
+}
+
+/// Future returned by `AsyncNetlinkConnection::send`
+pub struct Request<'a> {
+    conn: Option<&'a mut AsyncNetlinkConnection>,
+    msg: NetlinkPacket<'static>,
+}
+
+impl<'a> Future for Request<'a> {
+    type Output = Result<NetlinkStream<'a>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = &mut *self;
+        loop {
+            let sent = {
+                let conn = this.conn.as_ref().expect("Request polled after completion");
+                let mut guard = match conn.sock.poll_write_ready(cx) {
+                    Poll::Ready(Ok(guard)) => guard,
+                    Poll::Ready(Err(e)) => return Poll::Ready(Err(Error::Io(e))),
+                    Poll::Pending => return Poll::Pending,
+                };
+                let msg = &this.msg;
+                guard.try_io(|sock| sock.get_ref().send(msg.packet()))
+            };
+            match sent {
+                Ok(Ok(_)) => {
+                    let conn: &'a AsyncNetlinkConnection = this.conn.take().unwrap();
+                    return Poll::Ready(Ok(NetlinkStream {
+                        sock: &conn.sock,
+                        reader: NetlinkReader::with_filter(conn.sock.get_ref(), conn.seq, conn.port_id),
+                    }));
+                },
+                Ok(Err(e)) => return Poll::Ready(Err(Error::Io(e))),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+}
+
+/// Stream of reply packets, ends after NLMSG_DONE or an acknowledgement,
+/// error replies are yielded as `Error::Kernel`
+pub struct NetlinkStream<'a> {
+    sock: &'a AsyncFd<NetlinkSocket>,
+    reader: NetlinkReader<&'a NetlinkSocket>,
+}
+
+impl<'a> Stream for NetlinkStream<'a> {
+    type Item = Result<NetlinkPacket<'static>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            // the reader may still hold buffered packets, so try it
+            // before waiting for readiness
+            match this.reader.read_netlink() {
+                Ok(Some(pkt)) => return Poll::Ready(Some(Ok(pkt))),
+                Ok(None) => return Poll::Ready(None),
+                Err(Error::Io(ref e)) if e.kind() == io::ErrorKind::WouldBlock => {},
+                Err(e) => return Poll::Ready(Some(Err(e))),
+            }
+            match this.sock.poll_read_ready(cx) {
+                Poll::Ready(Ok(mut guard)) => guard.clear_ready(),
+                Poll::Ready(Err(e)) => return Poll::Ready(Some(Err(Error::Io(e)))),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
@@ -1,3 +1,5 @@
 //! Netlink packet functions
 pub mod netlink;
 pub mod route;
+#[cfg(feature = "async")]
+pub mod async_netlink;
@@ -6,8 +6,16 @@ use std::io;
 use std::io::{Read,BufRead,BufReader,Write};
 use std::marker::PhantomData;
 use pnet::packet::{Packet,PacketSize,FromPacket};
+use error::{Error,KernelError,Result};
+use netns::NetNs;
+use std::os::unix::io::{AsRawFd,RawFd};
 
-include!(concat!(env!("OUT_DIR"), "/netlink.rs"));
+#[cfg(feature = "mio")]
+use mio::{Interest,Registry,Token};
+#[cfg(feature = "mio")]
+use mio::event::Source;
+
+include!("netlink.rs.in");
 
 bitflags! {
     pub flags NetlinkMsgFlags: u16 {
@@ -33,6 +41,10 @@ bitflags! {
         const NLM_F_EXCL =    0x200,   /* Do not touch, if it exists   */
         const NLM_F_CREATE =  0x400,   /* Create, if it does not exist */
         const NLM_F_APPEND =  0x800,   /* Add to end of list           */
+
+        /* Flags for ACK message */
+        const NLM_F_CAPPED =  0x100,   /* request was capped */
+        const NLM_F_ACK_TLVS = 0x200,  /* extended ACK TVLs were included */
     }
 }
 
@@ -48,6 +60,12 @@ pub const NLMSG_ERROR: u16 = 2;
 pub const NLMSG_DONE: u16 = 3;
 pub const NLMSG_OVERRUN: u16 = 4;
 
+/* extended ACK attributes */
+pub const NLMSGERR_ATTR_UNUSED: u16 = 0;
+pub const NLMSGERR_ATTR_MSG: u16 = 1;
+pub const NLMSGERR_ATTR_OFFS: u16 = 2;
+pub const NLMSGERR_ATTR_COOKIE: u16 = 3;
+
 
 impl<'a> NetlinkIterable<'a> {
     pub fn new(buf: &'a [u8]) -> Self {
@@ -86,7 +104,6 @@ fn read_ip_link_dump_2() {
     let mut r = BufReader::new(f);
     let mut reader = NetlinkReader::new(&mut r);
     while let Ok(Some(pkt)) = reader.read_netlink() {
-        let pkt = pkt.get_packet();
         println!("{:?}", pkt);
         if pkt.get_kind() == NLMSG_DONE {
             break;
@@ -105,17 +122,33 @@ fn read_ip_link_sock() {
     let mut r = NetlinkSocket::bind(NetlinkProtocol::Route, 0 as u32).unwrap();
     let mut reader = NetlinkReader::new(&mut r);
     while let Ok(Some(pkt)) = reader.read_netlink() {
-        let pkt = pkt.get_packet();
         println!("{:?}", pkt);
     }
 }
 
+/// Returns message bytes of `pkt` bounded by its length field
+pub fn message_bytes<'a>(pkt: &'a NetlinkPacket) -> &'a [u8] {
+    let data = pkt.packet();
+    &data[..::std::cmp::min(pkt.get_length() as usize, data.len())]
+}
+
+/// Default and minimal receive buffer size of `NetlinkReader`
+pub const NETLINK_RECV_SIZE: usize = 32768;
+
 /// Netlink packet parser
+///
+/// Datagrams are received into a buffer of `recv_size` bytes, it grows
+/// when the socket reports a bigger datagram (see `NetlinkSocket::recv`).
 pub struct NetlinkReader<R: Read> {
     reader: R,
     buf: Vec<u8>,
+    /// end of received data in `buf`, the rest is kept for reuse
+    filled: usize,
     read_at: usize,
+    recv_size: usize,
     state: NetlinkReaderState,
+    filter: Option<(u32, u32)>,
+    interrupted: bool,
 }
 
 enum NetlinkReaderState {
@@ -130,21 +163,67 @@ impl<R: Read> NetlinkReader<R> {
         NetlinkReader {
             reader: reader,
             buf: vec![],
+            filled: 0,
             read_at: 0,
+            recv_size: NETLINK_RECV_SIZE,
             state: NetlinkReaderState::NeedMore,
+            filter: None,
+            interrupted: false,
         }
     }
 
+    /// Creates a reader which only returns replies to the request
+    /// with sequence number `seq` sent from port `port_id`.
+    ///
+    /// Other packets, e.g. multicast notifications or leftovers
+    /// of an aborted dump, are skipped.
+    pub fn with_filter(reader: R, seq: u32, port_id: u32) -> Self {
+        let mut reader = Self::new(reader);
+        reader.filter = Some((seq, port_id));
+        reader
+    }
+
+    /// Returns a reference to the underlying reader
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Returns a mutable reference to the underlying reader
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Discards buffered packets and resumes reading after
+    /// the end of a reply or an error
+    pub fn reset(&mut self) {
+        self.filled = 0;
+        self.read_at = 0;
+        self.state = NetlinkReaderState::NeedMore;
+        self.interrupted = false;
+    }
+
+    /// Sets receive buffer size, values below `NETLINK_RECV_SIZE` are rounded up
+    pub fn set_recv_size(&mut self, size: usize) {
+        self.recv_size = ::std::cmp::max(size, NETLINK_RECV_SIZE);
+    }
+
+    /// Receive buffer size
+    pub fn recv_size(&self) -> usize {
+        self.recv_size
+    }
+
+    /// Whether any packet read so far was flagged with NLM_F_DUMP_INTR
+    pub fn is_interrupted(&self) -> bool {
+        self.interrupted
+    }
+
     /// Read to end ignoring everything but errors
-    pub fn read_to_end(self) -> io::Result<()> {
-        for pkt in self.into_iter() {
-            if pkt.get_kind() == NLMSG_ERROR {
-                let err = NetlinkErrorPacket::new(pkt.payload()).unwrap();
-                if err.get_error() != 0 {
-                    return Err(io::Error::from_raw_os_error(-(err.get_error() as i32)));
-                } else {
-                    break; // ACK
-                }
+    pub fn read_to_end(mut self) -> Result<()> {
+        loop {
+            match try!(self.read_netlink()) {
+                Some(ref pkt) if pkt.get_kind() == NLMSG_ERROR => break, // ACK
+                Some(_) => continue,
+                None => break,
             }
         }
         Ok(())
@@ -152,7 +231,7 @@ impl<R: Read> NetlinkReader<R> {
 }
 
 impl<R: Read> ::std::iter::IntoIterator for NetlinkReader<R> {
-    type Item = NetlinkPacket<'static>;
+    type Item = Result<NetlinkPacket<'static>>;
     type IntoIter = NetlinkBufIterator<R>;
 
     fn into_iter(self) -> Self::IntoIter {
@@ -161,22 +240,82 @@ impl<R: Read> ::std::iter::IntoIterator for NetlinkReader<R> {
 }
 
 impl<R: Read> NetlinkReader<R> {
-    pub fn read_netlink(&mut self) -> io::Result<Option<NetlinkPacket<'static>>> {
+    /// Returns next packet or `None` when the reply is complete.
+    ///
+    /// Acknowledgements (NLMSG_ERROR with zero code) are returned as is,
+    /// error replies are converted into `Error::Kernel`. If the kernel
+    /// flagged the dump with NLM_F_DUMP_INTR, `Error::DumpInterrupted`
+    /// is returned instead of NLMSG_DONE.
+    pub fn read_netlink(&mut self) -> Result<Option<NetlinkPacket<'static>>> {
+        match try!(self.next_packet()) {
+            Some(pkt) => Ok(NetlinkPacket::owned(pkt.packet().to_owned())),
+            None => Ok(None),
+        }
+    }
+
+    /// Same as `read_netlink` but the packet borrows the receive buffer,
+    /// it stays valid until the next call
+    pub fn next_packet(&mut self) -> Result<Option<NetlinkPacket>> {
+        match try!(self.next_message()) {
+            Some(msg) => Ok(NetlinkPacket::new(msg)),
+            None => Ok(None),
+        }
+    }
+
+    /// Same as `next_packet` but returns raw message bytes, the slice
+    /// is exactly as long as the message header says
+    pub fn next_message(&mut self) -> Result<Option<&[u8]>> {
+        match try!(self.next_range()) {
+            Some((start, len)) => Ok(Some(&self.buf[start..start + len])),
+            None => Ok(None),
+        }
+    }
+
+    /// Parses next packet and returns its position in `buf`
+    fn next_range(&mut self) -> Result<Option<(usize, usize)>> {
         loop {
             match self.state {
                 NetlinkReaderState::NeedMore => {
-                    let mut buf = [0; 4096];
-                    match self.reader.read(&mut buf) {
+                    // drop consumed packets before reading the next datagram
+                    let start = self.filled - self.read_at;
+                    if self.read_at > 0 {
+                        let (read_at, filled) = (self.read_at, self.filled);
+                        self.buf.copy_within(read_at..filled, 0);
+                        self.read_at = 0;
+                    }
+                    // the buffer only grows, so it's zeroed once
+                    if self.buf.len() < start + self.recv_size {
+                        self.buf.resize(start + self.recv_size, 0);
+                    }
+                    let res = self.reader.read(&mut self.buf[start..start + self.recv_size]);
+                    self.filled = start + *res.as_ref().unwrap_or(&0);
+                    match res {
                         Ok(0) => {
                             self.state = NetlinkReaderState::Done;
+                            if self.filled > 0 {
+                                return Err(Error::Truncated);
+                            }
                             return Ok(None);
                         },
-                        Ok(len) =>{
-                            self.buf.extend_from_slice(&buf[0..len]);
-                        },
+                        Ok(_) => {},
                         Err(e) => {
-                            self.state = NetlinkReaderState::Error;
-                            return Err(e);
+                            // a non-blocking socket has no data yet, the caller
+                            // may retry once it becomes readable
+                            if e.kind() == io::ErrorKind::WouldBlock ||
+                               e.kind() == io::ErrorKind::Interrupted {
+                                return Err(Error::Io(e));
+                            }
+                            match Error::from(e) {
+                                // the datagram is still queued, retry with a bigger buffer
+                                Error::DatagramTruncated { len, capacity } if capacity < len => {
+                                    self.recv_size = len;
+                                },
+                                // including DatagramLost, the reply can't be completed
+                                e => {
+                                    self.state = NetlinkReaderState::Error;
+                                    return Err(e);
+                                },
+                            }
                         }
                     }
                 },
@@ -185,31 +324,65 @@ impl<R: Read> NetlinkReader<R> {
                 NetlinkReaderState::Parsing => { },
             }
             loop {
-                if let Some(pkt) = NetlinkPacket::new(&self.buf[self.read_at..]) {
-                    let len = ::util::align(pkt.get_length() as usize);
-                    if len == 0 {
-                        return Ok(None);
+                if let Some(pkt) = NetlinkPacket::new(&self.buf[self.read_at..self.filled]) {
+                    let pkt_len = pkt.get_length() as usize;
+                    if pkt_len < NetlinkPacket::minimum_packet_size() {
+                        self.state = NetlinkReaderState::Error;
+                        return Err(Error::Malformed("netlink message length"));
+                    }
+                    if self.read_at + pkt_len > self.filled {
+                        // rest of the message is not read yet
+                        self.state = NetlinkReaderState::NeedMore;
+                        break;
+                    }
+                    let len = ::std::cmp::min(::util::align(pkt_len), self.filled - self.read_at);
+                    if let Some((seq, port_id)) = self.filter {
+                        if pkt.get_seq() != seq || pkt.get_pid() != port_id {
+                            self.read_at += len;
+                            continue;
+                        }
+                    }
+                    if pkt.get_flags().contains(NLM_F_DUMP_INTR) {
+                        self.interrupted = true;
                     }
                     match pkt.get_kind() {
                         NLMSG_ERROR => {
-                            self.state = NetlinkReaderState::Error;
+                            let err = match KernelError::from_reply(&pkt) {
+                                Some(err) => err,
+                                None => {
+                                    self.state = NetlinkReaderState::Error;
+                                    return Err(Error::Truncated);
+                                },
+                            };
+                            if err.errno() != 0 {
+                                self.state = NetlinkReaderState::Error;
+                                self.read_at += len;
+                                return Err(Error::Kernel(err));
+                            }
+                            self.state = NetlinkReaderState::Done;
                         },
                         NLMSG_OVERRUN => {
-                            panic!("overrun!");
+                            self.state = NetlinkReaderState::Error;
+                            return Err(Error::Overrun);
                         },
                         NLMSG_DONE => {
                             self.state = NetlinkReaderState::Done;
+                            if self.interrupted {
+                                self.read_at += len;
+                                return Err(Error::DumpInterrupted);
+                            }
                         },
                         NLMSG_NOOP => {
-                            println!("noop")
+                            self.read_at += len;
+                            continue;
                         },
                         _ => {
                             self.state = NetlinkReaderState::Parsing;
                         },
                     }
-                    let slot = NetlinkPacket::owned(self.buf[self.read_at..self.read_at + pkt.get_length() as usize].to_owned()).unwrap();
+                    let start = self.read_at;
                     self.read_at += len;
-                    return Ok(Some(slot));
+                    return Ok(Some((start, pkt_len)));
                 } else {
                     self.state = NetlinkReaderState::NeedMore;
                     break;
@@ -224,31 +397,149 @@ pub struct NetlinkBufIterator<R: Read> {
 }
 
 impl<R: Read> Iterator for NetlinkBufIterator<R> {
-    type Item = NetlinkPacket<'static>;
+    type Item = Result<NetlinkPacket<'static>>;
 
     fn next(&mut self) -> Option<Self::Item> {
         match self.reader.read_netlink() {
-            Ok(Some(slot)) => Some(slot),
-            _ => None,
+            Ok(Some(slot)) => Some(Ok(slot)),
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
         }
     }
 }
 
+/// What to do when a dump is interrupted by a concurrent change
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum DumpPolicy {
+    /// Fail with `Error::DumpInterrupted`
+    Error,
+    /// Restart the dump up to `n` times before failing
+    /// with `Error::DumpInterrupted`
+    Retry(usize),
+}
+
 /// NetlinkConnection represents active netlink connection
 pub struct NetlinkConnection {
     sock: NetlinkSocket,
+    port_id: u32,
+    seq: u32,
 }
 
 impl NetlinkConnection {
-    pub fn new() -> Self {
-        NetlinkConnection {
-            sock: NetlinkSocket::bind(NetlinkProtocol::Route, 0 as u32).unwrap(),
+    pub fn new() -> Result<Self> {
+        let sock = try!(NetlinkSocket::bind(NetlinkProtocol::Route, 0 as u32));
+        Self::from_socket(sock)
+    }
+
+    /// Opens a connection inside network namespace `netns`,
+    /// it can be used from any namespace
+    pub fn new_in_netns(netns: &NetNs) -> Result<Self> {
+        let sock = try!(NetlinkSocket::bind_in_netns(NetlinkProtocol::Route, 0 as u32, netns));
+        Self::from_socket(sock)
+    }
+
+    fn from_socket(mut sock: NetlinkSocket) -> Result<Self> {
+        let port_id = try!(sock.port_id());
+        // not supported before Linux 4.12, plain errno replies are fine then
+        let _ = sock.enable_ext_ack();
+        Ok(NetlinkConnection {
+            sock: sock,
+            port_id: port_id,
+            seq: 0,
+        })
+    }
+
+    /// Port id assigned to the underlying socket
+    pub fn port_id(&self) -> u32 {
+        self.port_id
+    }
+
+    /// Sequence number of the last request sent
+    pub fn seq(&self) -> u32 {
+        self.seq
+    }
+
+    /// Sends `msg` and returns a reader for its replies
+    ///
+    /// The message is stamped with the next sequence number and
+    /// the connection's port id, the reader skips anything else.
+    pub fn send<'a,'b>(&'a mut self, msg: NetlinkPacket<'b>) -> Result<NetlinkReader<&'a mut NetlinkConnection>> {
+        try!(self.write(msg.packet()));
+        let (seq, port_id) = (self.seq, self.port_id);
+        Ok(NetlinkReader::with_filter(self, seq, port_id))
+    }
+
+    /// Sends dump request `req` and collects all replies
+    ///
+    /// The result is a consistent snapshot: an interrupted dump is
+    /// either restarted or reported according to `policy`.
+    pub fn dump(&mut self, req: &NetlinkPacket, policy: DumpPolicy) -> Result<Vec<NetlinkPacket<'static>>> {
+        let mut retries = match policy {
+            DumpPolicy::Error => 0,
+            DumpPolicy::Retry(n) => n,
+        };
+        loop {
+            match self.dump_once(req) {
+                Err(Error::DumpInterrupted) if retries > 0 => retries -= 1,
+                res => return res,
+            }
+        }
+    }
+
+    fn dump_once(&mut self, req: &NetlinkPacket) -> Result<Vec<NetlinkPacket<'static>>> {
+        let mut reader = try!(self.send(NetlinkPacket::new(req.packet()).unwrap()));
+        let mut pkts = vec![];
+        while let Some(pkt) = try!(reader.read_netlink()) {
+            if pkt.get_kind() != NLMSG_DONE {
+                pkts.push(pkt);
+            }
+        }
+        if reader.is_interrupted() {
+            return Err(Error::DumpInterrupted);
         }
+        Ok(pkts)
+    }
+
+    /// Subscribes to multicast group `group`, see `NetlinkSocket::join_group`
+    ///
+    /// Readers returned from `send` skip notifications caused by others,
+    /// but the ones caused by our own request carry its sequence number
+    /// and port id, so they are returned along with the reply. Use a
+    /// separate connection, e.g. `Monitor`, to follow notifications.
+    pub fn join_group<G: Into<u32>>(&mut self, group: G) -> io::Result<()> {
+        self.sock.join_group(group)
     }
 
-    pub fn send<'a,'b>(&'a mut self, msg: NetlinkPacket<'b>) -> NetlinkReader<&'a mut NetlinkConnection> {
-        self.sock.send(msg.packet()).unwrap();
-        NetlinkReader::new(self)
+    /// Unsubscribes from multicast group `group`
+    pub fn leave_group<G: Into<u32>>(&mut self, group: G) -> io::Result<()> {
+        self.sock.leave_group(group)
+    }
+
+    /// Switch the underlying socket to non-blocking mode,
+    /// see `NetlinkSocket::set_nonblocking`
+    pub fn set_nonblocking(&mut self, nonblocking: bool) -> io::Result<()> {
+        self.sock.set_nonblocking(nonblocking)
+    }
+}
+
+impl AsRawFd for NetlinkConnection {
+    fn as_raw_fd(&self) -> RawFd {
+        self.sock.as_raw_fd()
+    }
+}
+
+#[cfg(feature = "mio")]
+impl Source for NetlinkConnection {
+    fn register(&mut self, registry: &Registry, token: Token, interests: Interest) -> io::Result<()> {
+        self.sock.register(registry, token, interests)
+    }
+
+    fn reregister(&mut self, registry: &Registry, token: Token, interests: Interest) -> io::Result<()> {
+        self.sock.reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        self.sock.deregister(registry)
     }
 }
 
@@ -259,8 +550,16 @@ impl ::std::io::Read for NetlinkConnection {
 }
 
 impl ::std::io::Write for NetlinkConnection {
+    /// Sends a netlink message, its `pid` is set to the connection's port id
+    /// and `seq` to the next sequence number
     fn write(&mut self, buf: &[u8]) -> ::std::io::Result<usize> {
-        self.sock.send(buf)
+        let mut buf = buf.to_owned();
+        if let Some(mut pkt) = MutableNetlinkPacket::new(&mut buf) {
+            self.seq = self.seq.wrapping_add(1);
+            pkt.set_seq(self.seq);
+            pkt.set_pid(self.port_id);
+        }
+        self.sock.send(&buf)
     }
 
     fn flush(&mut self) -> ::std::io::Result<()> {
@@ -315,3 +614,169 @@ impl NetlinkRequestBuilder {
         NetlinkPacket::owned(self.data).unwrap()
     }
 }
+
+#[test]
+fn grow_recv_buffer() {
+    use std::io::Read;
+    use error::Error;
+
+    /// Returns a single NLMSG_DONE in a datagram bigger than the default buffer
+    struct BigDatagram {
+        sent: bool,
+    }
+
+    impl Read for BigDatagram {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            const LEN: usize = NETLINK_RECV_SIZE + 16;
+            if self.sent {
+                return Ok(0);
+            }
+            if buf.len() < LEN {
+                let err = Error::DatagramTruncated { len: LEN, capacity: buf.len() };
+                return Err(io::Error::new(io::ErrorKind::InvalidData, err));
+            }
+            let done = NetlinkRequestBuilder::new(NLMSG_DONE, NetlinkMsgFlags::empty()).build();
+            buf[..16].copy_from_slice(done.packet());
+            self.sent = true;
+            Ok(16)
+        }
+    }
+
+    let mut reader = NetlinkReader::new(BigDatagram { sent: false });
+    let pkt = reader.read_netlink().unwrap().unwrap();
+    assert_eq!(pkt.get_kind(), NLMSG_DONE);
+    assert_eq!(reader.recv_size(), NETLINK_RECV_SIZE + 16);
+}
+
+#[test]
+fn reuse_recv_buffer() {
+    use std::io::Read;
+
+    /// Returns one datagram per read
+    struct Datagrams(Vec<Vec<u8>>);
+
+    impl Read for Datagrams {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() {
+                return Ok(0);
+            }
+            let datagram = self.0.remove(0);
+            buf[..datagram.len()].copy_from_slice(&datagram);
+            Ok(datagram.len())
+        }
+    }
+
+    let datagrams = [16, 16, NLMSG_DONE].iter().map(|&kind| {
+        NetlinkRequestBuilder::new(kind, NLM_F_MULTI).build().packet().to_owned()
+    }).collect();
+    let mut reader = NetlinkReader::new(Datagrams(datagrams));
+    assert_eq!(reader.read_netlink().unwrap().unwrap().get_kind(), 16);
+    let buf = reader.buf.as_ptr();
+    assert_eq!(reader.read_netlink().unwrap().unwrap().get_kind(), 16);
+    assert_eq!(reader.read_netlink().unwrap().unwrap().get_kind(), NLMSG_DONE);
+    assert!(reader.read_netlink().unwrap().is_none());
+    // consumed datagrams are overwritten in place
+    assert_eq!((reader.buf.as_ptr(), reader.buf.len()), (buf, NETLINK_RECV_SIZE));
+}
+
+#[test]
+fn lost_datagram() {
+    use std::io::Read;
+    use error::Error;
+
+    /// Fails the first read as if the datagram was cut,
+    /// NLMSG_DONE would follow
+    struct LostDatagram {
+        reads: usize,
+    }
+
+    impl Read for LostDatagram {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.reads += 1;
+            if self.reads == 1 {
+                let err = Error::DatagramLost { len: buf.len() + 16, capacity: buf.len() };
+                return Err(io::Error::new(io::ErrorKind::InvalidData, err));
+            }
+            let done = NetlinkRequestBuilder::new(NLMSG_DONE, NetlinkMsgFlags::empty()).build();
+            buf[..16].copy_from_slice(done.packet());
+            Ok(16)
+        }
+    }
+
+    let mut reader = NetlinkReader::new(LostDatagram { reads: 0 });
+    match reader.read_netlink() {
+        Err(Error::DatagramLost { .. }) => {},
+        res => panic!("unexpected {:?}", res.map(|_| ())),
+    }
+    // no retry, the lost datagram may have been the end of the reply
+    assert!(reader.read_netlink().unwrap().is_none());
+    assert_eq!(reader.get_ref().reads, 1);
+    assert_eq!(reader.recv_size(), NETLINK_RECV_SIZE);
+}
+
+#[test]
+fn dump_interrupted() {
+    use error::Error;
+
+    let mut data = vec![];
+    for &(kind, flags) in &[(16, NLM_F_MULTI | NLM_F_DUMP_INTR), (NLMSG_DONE, NLM_F_MULTI)] {
+        let pkt = NetlinkRequestBuilder::new(kind, flags).build();
+        data.extend_from_slice(pkt.packet());
+    }
+    let mut reader = NetlinkReader::new(io::Cursor::new(data));
+    assert_eq!(reader.read_netlink().unwrap().unwrap().get_kind(), 16);
+    assert!(reader.is_interrupted());
+    match reader.read_netlink() {
+        Err(Error::DumpInterrupted) => {},
+        res => panic!("unexpected {:?}", res.map(|_| ())),
+    }
+    assert!(reader.read_netlink().unwrap().is_none());
+}
+
+#[test]
+fn filter_foreign_replies() {
+    let mut data = vec![];
+    // a foreign DONE would end the reply early if it was not skipped
+    for &(kind, seq, pid) in &[(16, 7, 99), (NLMSG_DONE, 8, 42), (18, 7, 42), (NLMSG_DONE, 7, 42)] {
+        let mut pkt = NetlinkRequestBuilder::new(kind, NLM_F_MULTI).build().packet().to_owned();
+        {
+            let mut pkt = MutableNetlinkPacket::new(&mut pkt).unwrap();
+            pkt.set_seq(seq);
+            pkt.set_pid(pid);
+        }
+        data.extend_from_slice(&pkt);
+    }
+    let mut reader = NetlinkReader::with_filter(io::Cursor::new(data), 7, 42);
+    let pkt = reader.read_netlink().unwrap().unwrap();
+    assert_eq!((pkt.get_kind(), pkt.get_seq(), pkt.get_pid()), (18, 7, 42));
+    assert_eq!(reader.read_netlink().unwrap().unwrap().get_kind(), NLMSG_DONE);
+    assert!(reader.read_netlink().unwrap().is_none());
+}
+
+#[test]
+fn join_and_leave_groups() {
+    use packet::route::RtnlGroup;
+
+    // bitmap of joined groups, NETLINK_LIST_MEMBERSHIPS
+    fn memberships(conn: &NetlinkConnection) -> Vec<u32> {
+        let mut mask = [0u32; 2];
+        let mut len = mem::size_of_val(&mask) as libc::socklen_t;
+        let res = unsafe {
+            libc::getsockopt(conn.as_raw_fd(), 270, 9, mask.as_mut_ptr() as *mut libc::c_void, &mut len)
+        };
+        assert_eq!(res, 0);
+        (1..65).filter(|group| mask[(group - 1) / 32] & (1 << ((group - 1) % 32)) != 0)
+            .map(|group| group as u32).collect()
+    }
+
+    let mut conn = NetlinkConnection::new().unwrap();
+    assert!(memberships(&conn).is_empty());
+    // groups above 32 do not fit the bind mask
+    conn.join_group(RtnlGroup::Link).unwrap();
+    conn.join_group(RtnlGroup::Stats).unwrap();
+    assert_eq!(memberships(&conn), vec![1, 36]);
+    conn.leave_group(RtnlGroup::Link).unwrap();
+    assert_eq!(memberships(&conn), vec![36]);
+    conn.leave_group(RtnlGroup::Stats).unwrap();
+    assert!(memberships(&conn).is_empty());
+}
@@ -1,12 +1,14 @@
 //! Address operations
 use packet::route::{IfAddrCacheInfoPacket,MutableIfInfoPacket,IfAddrPacket,MutableIfAddrPacket,RtAttrIterator,RtAttrPacket,MutableRtAttrPacket,RtAttrMtuPacket};
 use packet::route::link::Link;
+use packet::route::nla::{Nla,NlaIterator,FromNla,emit_nla};
 use packet::netlink::{MutableNetlinkPacket,NetlinkPacket,NetlinkErrorPacket};
 use packet::netlink::{NLM_F_ACK,NLM_F_REQUEST,NLM_F_DUMP,NLM_F_MATCH,NLM_F_EXCL,NLM_F_CREATE};
 use packet::netlink::{NLMSG_NOOP,NLMSG_ERROR,NLMSG_DONE,NLMSG_OVERRUN};
 use packet::netlink::{NetlinkBufIterator,NetlinkReader,NetlinkRequestBuilder};
 use socket::{NetlinkSocket,NetlinkProtocol};
-use packet::netlink::NetlinkConnection;
+use packet::netlink::{NetlinkConnection,DumpPolicy};
+use error::{Error,Result};
 use pnet::packet::MutablePacket;
 use pnet::packet::Packet;
 use pnet::packet::PacketSize;
@@ -16,6 +18,13 @@ use std::io::{Read,Write,Cursor,self};
 use byteorder::{LittleEndian, BigEndian, ReadBytesExt};
 use std::net::{Ipv4Addr,Ipv6Addr};
 
+#[cfg(feature = "async")]
+use packet::async_netlink::AsyncNetlinkConnection;
+#[cfg(feature = "async")]
+use futures::future::{self,BoxFuture,FutureExt,TryFutureExt};
+#[cfg(feature = "async")]
+use futures::stream::{BoxStream,StreamExt,TryStreamExt};
+
 pub const RTM_NEWADDR: u16 = 20;
 pub const RTM_DELADDR: u16 = 21;
 pub const RTM_GETADDR: u16 = 22;
@@ -30,21 +39,40 @@ pub const RTM_GETADDR: u16 = 22;
    Intermediate values are also possible f.e. interior routes
    could be assigned a value between UNIVERSE and LINK.
 */
-#[derive(Debug,Copy,Clone)]
-#[repr(u8)]
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
 pub enum Scope {
-    Global=0,
+    Global,
     /* User defined values  */
-    Site=200,
-    Link=253,
-    Host=254,
-    Nowhere=255
+    Site,
+    Link,
+    Host,
+    Nowhere,
+    /// Intermediate value without a name
+    Other(u8),
 }
 
 impl Scope {
     pub fn new(val: u8) -> Self {
-        use std::mem;
-        unsafe { mem::transmute(val) }
+        match val {
+            0 => Scope::Global,
+            200 => Scope::Site,
+            253 => Scope::Link,
+            254 => Scope::Host,
+            255 => Scope::Nowhere,
+            _ => Scope::Other(val),
+        }
+    }
+
+    /// RT_SCOPE_* value
+    pub fn value(&self) -> u8 {
+        match *self {
+            Scope::Global => 0,
+            Scope::Site => 200,
+            Scope::Link => 253,
+            Scope::Host => 254,
+            Scope::Nowhere => 255,
+            Scope::Other(val) => val,
+        }
     }
 }
 
@@ -90,44 +118,30 @@ pub struct AddrsIterator<R: Read> {
 }
 
 impl<R: Read> Iterator for AddrsIterator<R> {
-    type Item = Addr;
+    type Item = Result<Addr>;
 
     fn next(&mut self) -> Option<Self::Item> {
         match self.iter.next() {
-            Some(pkt) => {
+            Some(Ok(pkt)) => {
                 let kind = pkt.get_kind();
                 if kind != RTM_NEWADDR {
                     return None;
                 }
-                return Some(Addr { packet: pkt });
+                return Some(Addr::from_packet(pkt));
             },
+            Some(Err(e)) => Some(Err(e)),
             None => None,
         }
     }
 }
 
 /// Abstract over IP versions
-#[derive(Eq,PartialEq)]
+#[derive(Eq,PartialEq,Hash,Clone,Copy)]
 pub enum IpAddr {
     V4(Ipv4Addr),
     V6(Ipv6Addr),
 }
 
-impl IpAddr {
-    fn bytes(&self) -> Vec<u8> {
-        match self {
-            &IpAddr::V4(ip) => {
-                let mut v = Vec::new();
-                v.extend_from_slice(&ip.octets()[..]);
-                v
-            },
-            &IpAddr::V6(ip) => {
-                panic!("not implemented"); /* FIXME */
-            }
-        }
-    }
-}
-
 impl From<Ipv6Addr> for IpAddr {
     fn from(addr: Ipv6Addr) -> Self {
         IpAddr::V6(addr)
@@ -151,84 +165,112 @@ impl ::std::fmt::Debug for IpAddr {
 
 /// Address operations trait
 pub trait Addresses where Self: Read + Write {
-    fn iter_addrs<'a>(&'a mut self, family: Option<u8>) -> io::Result<Box<Iterator<Item = Addr> + 'a>>;
-    fn get_link_addrs<'a,'b>(&'a mut self, family: Option<u8>, link: &'b Link) -> io::Result<Box<Iterator<Item = Addr> + 'a>>;
-    fn add_addr<'a,'b>(&'a mut self, link: &'b Link, addr: IpAddr, scope: Scope) -> io::Result<()>;
+    fn iter_addrs<'a>(&'a mut self, family: Option<u8>) -> Result<Box<Iterator<Item = Result<Addr>> + 'a>>;
+    fn get_link_addrs<'a,'b>(&'a mut self, family: Option<u8>, link: &'b Link) -> Result<Box<Iterator<Item = Result<Addr>> + 'a>>;
+    fn add_addr<'a,'b>(&'a mut self, link: &'b Link, addr: IpAddr, scope: Scope) -> Result<()>;
+    /// Returns a consistent snapshot of all addresses, see `DumpPolicy`
+    fn snapshot_addrs(&mut self, family: Option<u8>, policy: DumpPolicy) -> Result<Vec<Addr>>;
 }
 
 impl Addresses for NetlinkConnection {
     /// Iterate over all addresses
-    fn iter_addrs<'a>(&'a mut self, family: Option<u8>) -> io::Result<Box<Iterator<Item = Addr> + 'a>> {
-        let mut buf = vec![0; MutableIfInfoPacket::minimum_packet_size()];
-        let req = NetlinkRequestBuilder::new(RTM_GETADDR, NLM_F_DUMP)
-            .append({
-                let mut ifinfo = MutableIfInfoPacket::new(&mut buf).unwrap();
-                ifinfo.set_family(family.unwrap_or(0));
-                ifinfo
-            }).build();
-        try!(self.write(req.packet()));
-        let reader = NetlinkReader::new(self);
+    fn iter_addrs<'a>(&'a mut self, family: Option<u8>) -> Result<Box<Iterator<Item = Result<Addr>> + 'a>> {
+        let req = dump_addrs_request(family);
+        let reader = try!(self.send(req));
         let iter = AddrsIterator { iter: reader.into_iter() };
         Ok(Box::new(iter))
     }
 
     /// Iterate over `family` addresses for `link`
-    fn get_link_addrs<'a,'b>(&'a mut self, family: Option<u8>, link: &'b Link) -> io::Result<Box<Iterator<Item = Addr> + 'a>> {
+    fn get_link_addrs<'a,'b>(&'a mut self, family: Option<u8>, link: &'b Link) -> Result<Box<Iterator<Item = Result<Addr>> + 'a>> {
         let idx = link.get_index();
-        let mut buf = vec![0; MutableIfInfoPacket::minimum_packet_size()];
-        let req = NetlinkRequestBuilder::new(RTM_GETADDR, NLM_F_DUMP)
-            .append({
-                let mut ifinfo = MutableIfInfoPacket::new(&mut buf).unwrap();
-                ifinfo.set_family(family.unwrap_or(0));
-                ifinfo
-            }).build();
-        try!(self.write(req.packet()));
-        let reader = NetlinkReader::new(self);
+        let req = dump_addrs_request(family);
+        let reader = try!(self.send(req));
         let iter = AddrsIterator { iter: reader.into_iter() };
-        Ok(Box::new(iter.filter(move |addr| addr.with_ifaddr(|ifa| ifa.get_index() == idx))))
+        Ok(Box::new(iter.filter(move |addr| match *addr {
+            Ok(ref addr) => addr.get_link_index() == idx,
+            Err(_) => true,
+        })))
     }
 
     /// Add address `addr` to `link` with scope `scope`
-    fn add_addr<'a,'b>(&'a mut self, link: &'b Link, addr: IpAddr, scope: Scope) -> io::Result<()> {
-        let link_index = link.get_index();
-        let family = match addr {
-            IpAddr::V4(_) => 2,
-            IpAddr::V6(_) => 10,
-        };
-        let prefix_len = 32; /* XXX: FIXME */
-        let mut buf = vec![0; MutableIfAddrPacket::minimum_packet_size()];
-        let mut rta_buf = vec![0; MutableRtAttrPacket::minimum_packet_size() + 4];
-        let mut rta_buf1 = vec![0; MutableRtAttrPacket::minimum_packet_size() + 4];
-        let req = IfAddrRequestBuilder::new().with_ifa(|mut ifaddr| {
-                ifaddr.set_index(link_index);
-                ifaddr.set_family(family);
-                ifaddr.set_scope(scope);
-                ifaddr.set_prefix_len(prefix_len);
-        }).append({
-            {
-                let mut pkt = MutableRtAttrPacket::new(&mut rta_buf).unwrap();
-                pkt.set_rta_len(4 + 4 /* FIXME: hardcoded ipv4 */);
-                pkt.set_rta_type(IFA_ADDRESS);
-                let mut pl = pkt.payload_mut();
-                pl.copy_from_slice(&addr.bytes()[0..4]);
-            }
-            RtAttrPacket::new(&mut rta_buf).unwrap()
-        }).append({
-            {
-                let mut pkt = MutableRtAttrPacket::new(&mut rta_buf1).unwrap();
-                pkt.set_rta_len(4 + 4 /* FIXME: hardcoded ipv4 */);
-                pkt.set_rta_type(IFA_LOCAL);
-                let mut pl = pkt.payload_mut();
-                pl.copy_from_slice(&addr.bytes()[0..4]);
-            }
-            RtAttrPacket::new(&mut rta_buf1).unwrap()
-        }).build();
-        let req = NetlinkRequestBuilder::new(RTM_NEWADDR, NLM_F_CREATE | NLM_F_EXCL | NLM_F_ACK)
-            .append(req).build();
-        self.write(req.packet());
-        let reader = NetlinkReader::new(self);
+    fn add_addr<'a,'b>(&'a mut self, link: &'b Link, addr: IpAddr, scope: Scope) -> Result<()> {
+        let req = add_addr_request(link, addr, scope);
+        let reader = try!(self.send(req));
         reader.read_to_end()
     }
+
+    /// Returns a consistent snapshot of all `family` addresses
+    fn snapshot_addrs(&mut self, family: Option<u8>, policy: DumpPolicy) -> Result<Vec<Addr>> {
+        let pkts = try!(self.dump(&dump_addrs_request(family), policy));
+        pkts.into_iter()
+            .filter(|pkt| pkt.get_kind() == RTM_NEWADDR)
+            .map(Addr::from_packet)
+            .collect()
+    }
+}
+
+/// Async counterpart of `Addresses`
+#[cfg(feature = "async")]
+pub trait AsyncAddresses {
+    fn iter_addrs<'a>(&'a mut self, family: Option<u8>) -> BoxFuture<'a, Result<BoxStream<'a, Result<Addr>>>>;
+    fn get_link_addrs<'a,'b>(&'a mut self, family: Option<u8>, link: &'b Link) -> BoxFuture<'a, Result<BoxStream<'a, Result<Addr>>>>;
+    fn add_addr<'a,'b>(&'a mut self, link: &'b Link, addr: IpAddr, scope: Scope) -> BoxFuture<'a, Result<()>>;
+}
+
+#[cfg(feature = "async")]
+impl AsyncAddresses for AsyncNetlinkConnection {
+    /// Iterate over all addresses
+    fn iter_addrs<'a>(&'a mut self, family: Option<u8>) -> BoxFuture<'a, Result<BoxStream<'a, Result<Addr>>>> {
+        self.send(dump_addrs_request(family)).map_ok(|replies| {
+            replies.try_take_while(|pkt| future::ready(Ok(pkt.get_kind() == RTM_NEWADDR)))
+                .and_then(|pkt| future::ready(Addr::from_packet(pkt)))
+                .boxed()
+        }).boxed()
+    }
+
+    /// Iterate over `family` addresses for `link`
+    fn get_link_addrs<'a,'b>(&'a mut self, family: Option<u8>, link: &'b Link) -> BoxFuture<'a, Result<BoxStream<'a, Result<Addr>>>> {
+        let idx = link.get_index();
+        self.iter_addrs(family).map_ok(move |addrs| {
+            addrs.try_filter(move |addr| future::ready(addr.get_link_index() == idx))
+                .boxed()
+        }).boxed()
+    }
+
+    /// Add address `addr` to `link` with scope `scope`
+    fn add_addr<'a,'b>(&'a mut self, link: &'b Link, addr: IpAddr, scope: Scope) -> BoxFuture<'a, Result<()>> {
+        self.send_ack(add_addr_request(link, addr, scope))
+    }
+}
+
+fn dump_addrs_request(family: Option<u8>) -> NetlinkPacket<'static> {
+    let mut buf = vec![0; MutableIfInfoPacket::minimum_packet_size()];
+    NetlinkRequestBuilder::new(RTM_GETADDR, NLM_F_DUMP)
+        .append({
+            let mut ifinfo = MutableIfInfoPacket::new(&mut buf).unwrap();
+            ifinfo.set_family(family.unwrap_or(0));
+            ifinfo
+        }).build()
+}
+
+fn add_addr_request(link: &Link, addr: IpAddr, scope: Scope) -> NetlinkPacket<'static> {
+    let link_index = link.get_index();
+    let (family, prefix_len) = match addr {
+        IpAddr::V4(_) => (2, 32),
+        IpAddr::V6(_) => (10, 128),
+    };
+    let req = IfAddrRequestBuilder::new().with_ifa(|mut ifaddr| {
+            ifaddr.set_index(link_index);
+            ifaddr.set_family(family);
+            ifaddr.set_scope(scope);
+            ifaddr.set_prefix_len(prefix_len);
+    })
+    .nla(IFA_ADDRESS, addr)
+    .nla(IFA_LOCAL, addr)
+    .build();
+    NetlinkRequestBuilder::new(RTM_NEWADDR, NLM_F_CREATE | NLM_F_EXCL | NLM_F_ACK)
+        .append(req).build()
 }
 
 /// Address
@@ -238,6 +280,18 @@ pub struct Addr {
 }
 
 impl Addr {
+    /// Wraps RTM_NEWADDR or RTM_DELADDR message
+    pub fn from_packet(packet: NetlinkPacket<'static>) -> Result<Self> {
+        let kind = packet.get_kind();
+        if kind != RTM_NEWADDR && kind != RTM_DELADDR {
+            return Err(Error::Malformed("not an address message"));
+        }
+        if IfAddrPacket::new(packet.payload()).is_none() {
+            return Err(Error::Truncated);
+        }
+        Ok(Addr { packet: packet })
+    }
+
     /// get address family
     pub fn get_family(&self) -> u8 {
         self.with_ifaddr(|ifa| ifa.get_family())
@@ -270,35 +324,27 @@ impl Addr {
     /// but for point-to-point it is DESTINATION address,
     /// local address is supplied by get_local_ip().
     ///
-    pub fn get_ip(&self) -> Option<IpAddr> {
-        let family = self.with_ifaddr(|ifa| ifa.get_family());
-        self.with_rta(IFA_ADDRESS, |rta| {
-            Self::ip_from_family_and_bytes(family, rta.payload())
-        })
+    pub fn get_ip(&self) -> Result<Option<IpAddr>> {
+        self.get_ip_rta(IFA_ADDRESS)
     }
 
     /// See get_ip()
-    pub fn get_local_ip(&self) -> Option<IpAddr> {
-        let family = self.with_ifaddr(|ifa| ifa.get_family());
-        self.with_rta(IFA_LOCAL, |rta| {
-            Self::ip_from_family_and_bytes(family, rta.payload())
-        })
+    pub fn get_local_ip(&self) -> Result<Option<IpAddr>> {
+        self.get_ip_rta(IFA_LOCAL)
     }
 
     /// Get broadcast address
-    pub fn get_broadcast_ip(&self) -> Option<IpAddr> {
-        let family = self.with_ifaddr(|ifa| ifa.get_family());
-        self.with_rta(IFA_BROADCAST, |rta| {
-            Self::ip_from_family_and_bytes(family, rta.payload())
-        })
+    pub fn get_broadcast_ip(&self) -> Result<Option<IpAddr>> {
+        self.get_ip_rta(IFA_BROADCAST)
     }
 
-    pub fn get_label(&self) -> Option<String> {
-        use std::ffi::CStr;
-        self.with_rta(IFA_LABEL, |rta| {
-            let cstr = CStr::from_bytes_with_nul(rta.payload()).unwrap();
-            cstr.to_owned().into_string().unwrap()
-        })
+    pub fn get_label(&self) -> Result<Option<String>> {
+        self.get_nla(IFA_LABEL)
+    }
+
+    /// Decodes attribute `kind`, `None` if it is missing
+    pub fn get_nla<T: FromNla>(&self, kind: u16) -> Result<Option<T>> {
+        self.with_ifaddr(|ifa| NlaIterator::new(ifa.payload()).get(kind))
     }
 
     /* TODO: implement get_cache_info() */
@@ -316,33 +362,33 @@ impl Addr {
         )
     }
 
-    fn with_rta_iter<T,F>(&self, cb: F) -> T
-        where F: Fn(RtAttrIterator) -> T {
-            self.with_ifaddr(|ifa| {
-                cb(RtAttrIterator::new(ifa.payload()))
-            })
-    }
-
-    fn with_rta<T,F>(&self, rta_type: u16, cb: F) -> Option<T>
-        where F: Fn(RtAttrPacket) -> T {
-        self.with_rta_iter(|mut rti| {
-            rti.find(|rta| rta.get_rta_type() == rta_type).map(|rta| cb(rta))
-        })
+    fn get_ip_rta(&self, kind: u16) -> Result<Option<IpAddr>> {
+        let family = self.get_family();
+        match try!(self.get_nla::<Vec<u8>>(kind)) {
+            Some(bytes) => Self::ip_from_family_and_bytes(family, &bytes).map(Some),
+            None => Ok(None),
+        }
     }
 
     /// Extract an IP address from a buffer.
-    pub fn ip_from_family_and_bytes(family: u8, bytes: &[u8]) -> IpAddr {
-        let mut cur = Cursor::new(bytes);
+    pub fn ip_from_family_and_bytes(family: u8, bytes: &[u8]) -> Result<IpAddr> {
         match family {
-            2 /* AF_INET */ => IpAddr::V4(Ipv4Addr::from(cur.read_u32::<BigEndian>().unwrap())),
+            2 /* AF_INET */ => {
+                if bytes.len() != 4 {
+                    return Err(Error::Malformed("IPv4 address length"));
+                }
+                let mut cur = Cursor::new(bytes);
+                Ok(IpAddr::V4(Ipv4Addr::from(try!(cur.read_u32::<BigEndian>()))))
+            },
             10 /* AF_INET6 */ => {
+                if bytes.len() != 16 {
+                    return Err(Error::Malformed("IPv6 address length"));
+                }
                 let mut ip6addr: [u8;16] = [0;16];
-                &mut ip6addr[..].copy_from_slice(bytes);
-                IpAddr::V6(Ipv6Addr::from(ip6addr))
+                ip6addr[..].copy_from_slice(bytes);
+                Ok(IpAddr::V6(Ipv6Addr::from(ip6addr)))
             },
-            _ => {
-                panic!("not implemented")
-            }
+            _ => Err(Error::UnsupportedFamily(family)),
         }
     }
 
@@ -359,25 +405,15 @@ impl Addr {
             for rta in iter {
                 match rta.get_rta_type() {
                     IFA_ADDRESS | IFA_LOCAL | IFA_BROADCAST => {
-                        let mut cur = Cursor::new(rta.payload());
                         match rta.get_rta_type() {
                             IFA_ADDRESS => print!(" ├ ADDR: "),
                             IFA_LOCAL => print!(" ├ LOCAL: "),
                             IFA_BROADCAST => print!(" ├ BROADCAST: "),
                             _ => unreachable!(),
                         }
-                        match ifa.get_family() {
-                            2 => {
-                                println!("{}", Ipv4Addr::from(cur.read_u32::<BigEndian>().unwrap()));
-                            },
-                            10 => {
-                                let mut ip6addr: [u8;16] = [0;16];
-                                &mut ip6addr[..].copy_from_slice(rta.payload());
-                                println!("{}", Ipv6Addr::from(ip6addr));
-                            },
-                            _ => {
-                                println!("{:?}", rta.payload());
-                            }
+                        match Self::ip_from_family_and_bytes(ifa.get_family(), rta.payload()) {
+                            Ok(ip) => println!("{:?}", ip),
+                            Err(_) => println!("{:?}", rta.payload()),
                         }
                     },
                     /*
@@ -392,7 +428,7 @@ impl Addr {
                         println!(" ├ LABEL: {:?}", CStr::from_bytes_with_nul(rta.payload()));
                     },
                     IFA_CACHEINFO => {
-                        println!(" ├ CACHEINFO: {:?}", IfAddrCacheInfoPacket::new(rta.payload()).unwrap());
+                        println!(" ├ CACHEINFO: {:?}", IfAddrCacheInfoPacket::new(rta.payload()));
                     },
                     _ => println!(" ├ {:?}", rta),
                 }
@@ -400,7 +436,7 @@ impl Addr {
         }
     }
 
-    pub fn iter_addrs(conn: &mut NetlinkConnection) -> AddrsIterator<&mut NetlinkConnection> {
+    pub fn iter_addrs(conn: &mut NetlinkConnection) -> Result<AddrsIterator<&mut NetlinkConnection>> {
         let mut buf = vec![0; MutableIfInfoPacket::minimum_packet_size()];
         let req = NetlinkRequestBuilder::new(RTM_GETADDR, NLM_F_DUMP)
         .append({
@@ -408,8 +444,8 @@ impl Addr {
             ifinfo.set_family(0 /* AF_UNSPEC */);
             ifinfo
         }).build();
-        let mut reply = conn.send(req);
-        AddrsIterator { iter: reply.into_iter() }
+        let reply = try!(conn.send(req));
+        Ok(AddrsIterator { iter: reply.into_iter() })
     }
 }
 
@@ -443,19 +479,44 @@ impl IfAddrRequestBuilder {
         self
     }
 
+    /// Appends attribute `kind` with value `value`
+    pub fn nla<T: Nla>(mut self, kind: u16, value: T) -> Self {
+        emit_nla(&mut self.data, kind, &value);
+        self
+    }
+
     pub fn build(self) -> IfAddrPacket<'static> {
         IfAddrPacket::owned(self.data).unwrap()
     }
 }
 
+#[test]
+fn ip_from_bytes() {
+    assert!(Addr::ip_from_family_and_bytes(2, &[127, 0, 0, 1]).unwrap() == IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)));
+    assert!(Addr::ip_from_family_and_bytes(2, &[127, 0, 0]).is_err());
+    match Addr::ip_from_family_and_bytes(7, &[]) {
+        Err(Error::UnsupportedFamily(7)) => {},
+        r => panic!("unexpected {:?}", r),
+    }
+}
+
+#[test]
+fn scope_values() {
+    assert_eq!(Scope::new(253), Scope::Link);
+    assert_eq!(Scope::new(100), Scope::Other(100));
+    for val in 0..256 {
+        assert_eq!(Scope::new(val as u8).value(), val as u8);
+    }
+}
+
 #[test]
 fn dump_addrs() {
     use packet::netlink::NetlinkConnection;
     use packet::route::addr::Addresses;
 
-    let mut conn = NetlinkConnection::new();
+    let mut conn = NetlinkConnection::new().unwrap();
     for addr in conn.iter_addrs(None).unwrap() {
-        Addr::dump_addr(addr.packet.get_packet());
+        Addr::dump_addr(addr.unwrap().packet);
     }
 }
 
@@ -0,0 +1,753 @@
+//! Bridge devices and their ports
+//!
+//! # Example
+//! ```ignore
+//! let bridge = conn.create_link(BridgeSpec::new("br0").stp_state(true).up().into_link_spec())?;
+//! let port = conn.get_link_by_name("eth0")?.unwrap();
+//! conn.enslave_link(&bridge, &port)?;
+//! ```
+use packet::route::{IfInfoPacket,MutableIfInfoPacket};
+use packet::route::link::{Link,LinkChange,LinkSpec,LinkType,Links};
+use packet::route::link::{RTM_NEWLINK,RTM_DELLINK,RTM_GETLINK,RTM_SETLINK,IFLA_PROTINFO,IFLA_AF_SPEC,IFLA_EXT_MASK};
+use packet::route::nla::{Nla,NlaBuilder,NlaIterator};
+use packet::netlink::{NetlinkConnection,NetlinkPacket,NetlinkRequestBuilder,DumpPolicy};
+use packet::netlink::{NLM_F_ACK,NLM_F_DUMP};
+use error::{Error,Result};
+use byteorder::{ByteOrder,NativeEndian};
+use pnet::util::MacAddr;
+use std::time::Duration;
+
+pub const IFLA_BR_UNSPEC: u16 = 0;
+pub const IFLA_BR_FORWARD_DELAY: u16 = 1;
+pub const IFLA_BR_HELLO_TIME: u16 = 2;
+pub const IFLA_BR_MAX_AGE: u16 = 3;
+pub const IFLA_BR_AGEING_TIME: u16 = 4;
+pub const IFLA_BR_STP_STATE: u16 = 5;
+pub const IFLA_BR_PRIORITY: u16 = 6;
+pub const IFLA_BR_VLAN_FILTERING: u16 = 7;
+pub const IFLA_BR_VLAN_PROTOCOL: u16 = 8;
+pub const IFLA_BR_GROUP_FWD_MASK: u16 = 9;
+pub const IFLA_BR_ROOT_ID: u16 = 10;
+pub const IFLA_BR_BRIDGE_ID: u16 = 11;
+pub const IFLA_BR_ROOT_PORT: u16 = 12;
+pub const IFLA_BR_ROOT_PATH_COST: u16 = 13;
+pub const IFLA_BR_TOPOLOGY_CHANGE: u16 = 14;
+pub const IFLA_BR_TOPOLOGY_CHANGE_DETECTED: u16 = 15;
+pub const IFLA_BR_HELLO_TIMER: u16 = 16;
+pub const IFLA_BR_TCN_TIMER: u16 = 17;
+pub const IFLA_BR_TOPOLOGY_CHANGE_TIMER: u16 = 18;
+pub const IFLA_BR_GC_TIMER: u16 = 19;
+pub const IFLA_BR_GROUP_ADDR: u16 = 20;
+pub const IFLA_BR_FDB_FLUSH: u16 = 21;
+pub const IFLA_BR_MCAST_ROUTER: u16 = 22;
+pub const IFLA_BR_MCAST_SNOOPING: u16 = 23;
+pub const IFLA_BR_VLAN_DEFAULT_PVID: u16 = 39;
+
+pub const IFLA_BRPORT_UNSPEC: u16 = 0;
+pub const IFLA_BRPORT_STATE: u16 = 1;
+pub const IFLA_BRPORT_PRIORITY: u16 = 2;
+pub const IFLA_BRPORT_COST: u16 = 3;
+pub const IFLA_BRPORT_MODE: u16 = 4;
+pub const IFLA_BRPORT_GUARD: u16 = 5;
+pub const IFLA_BRPORT_PROTECT: u16 = 6;
+pub const IFLA_BRPORT_FAST_LEAVE: u16 = 7;
+pub const IFLA_BRPORT_LEARNING: u16 = 8;
+pub const IFLA_BRPORT_UNICAST_FLOOD: u16 = 9;
+pub const IFLA_BRPORT_PROXYARP: u16 = 10;
+pub const IFLA_BRPORT_LEARNING_SYNC: u16 = 11;
+pub const IFLA_BRPORT_PROXYARP_WIFI: u16 = 12;
+pub const IFLA_BRPORT_MCAST_FLOOD: u16 = 27;
+pub const IFLA_BRPORT_BCAST_FLOOD: u16 = 30;
+pub const IFLA_BRPORT_NEIGH_SUPPRESS: u16 = 32;
+pub const IFLA_BRPORT_ISOLATED: u16 = 33;
+
+/* IFLA_AF_SPEC attributes for AF_BRIDGE */
+pub const IFLA_BRIDGE_FLAGS: u16 = 0;
+pub const IFLA_BRIDGE_MODE: u16 = 1;
+pub const IFLA_BRIDGE_VLAN_INFO: u16 = 2;
+
+/// IFLA_BRIDGE_FLAGS value addressing the bridge a port is enslaved to,
+/// the default
+pub const BRIDGE_FLAGS_MASTER: u16 = 1;
+/// IFLA_BRIDGE_FLAGS value addressing the device itself
+pub const BRIDGE_FLAGS_SELF: u16 = 2;
+
+/// IFLA_EXT_MASK value requesting per-VLAN entries
+pub const RTEXT_FILTER_BRVLAN: u32 = 2;
+
+pub const AF_BRIDGE: u8 = 7;
+
+bitflags! {
+    pub flags BridgeVlanFlags: u16 {
+        /// operate on the bridge device, not the port
+        const BRIDGE_VLAN_INFO_MASTER = 0x1,
+        /// untagged ingress frames are assigned to this VLAN
+        const BRIDGE_VLAN_INFO_PVID = 0x2,
+        /// egress frames leave untagged
+        const BRIDGE_VLAN_INFO_UNTAGGED = 0x4,
+        /// first VLAN of a range
+        const BRIDGE_VLAN_INFO_RANGE_BEGIN = 0x8,
+        /// last VLAN of a range
+        const BRIDGE_VLAN_INFO_RANGE_END = 0x10,
+        /// global bridge VLAN entry
+        const BRIDGE_VLAN_INFO_BRENTRY = 0x20,
+    }
+}
+
+/// Bridge timers are exchanged in USER_HZ ticks
+const USER_HZ: u64 = 100;
+
+fn to_ticks(duration: Duration) -> u32 {
+    (duration.as_secs() * USER_HZ + duration.subsec_nanos() as u64 * USER_HZ / 1_000_000_000) as u32
+}
+
+fn from_ticks(ticks: u32) -> Duration {
+    let ticks = ticks as u64;
+    Duration::new(ticks / USER_HZ, ((ticks % USER_HZ) * (1_000_000_000 / USER_HZ)) as u32)
+}
+
+/// Bridge to create, unset options keep the kernel's defaults
+pub struct BridgeSpec {
+    link: LinkSpec,
+    data: NlaBuilder,
+}
+
+impl BridgeSpec {
+    pub fn new(name: &str) -> Self {
+        BridgeSpec {
+            link: LinkSpec::new(name, LinkType::Bridge),
+            data: NlaBuilder::new(),
+        }
+    }
+
+    /// Enables the kernel spanning tree protocol
+    pub fn stp_state(mut self, enable: bool) -> Self {
+        self.data = self.data.append(IFLA_BR_STP_STATE, enable as u32);
+        self
+    }
+
+    pub fn forward_delay(mut self, delay: Duration) -> Self {
+        self.data = self.data.append(IFLA_BR_FORWARD_DELAY, to_ticks(delay));
+        self
+    }
+
+    /// Time after which learned FDB entries expire
+    pub fn ageing_time(mut self, time: Duration) -> Self {
+        self.data = self.data.append(IFLA_BR_AGEING_TIME, to_ticks(time));
+        self
+    }
+
+    pub fn vlan_filtering(mut self, enable: bool) -> Self {
+        self.data = self.data.append(IFLA_BR_VLAN_FILTERING, enable as u8);
+        self
+    }
+
+    /// VLAN assigned to new ports, 0 disables it
+    pub fn default_pvid(mut self, vid: u16) -> Self {
+        self.data = self.data.append(IFLA_BR_VLAN_DEFAULT_PVID, vid);
+        self
+    }
+
+    pub fn mcast_snooping(mut self, enable: bool) -> Self {
+        self.data = self.data.append(IFLA_BR_MCAST_SNOOPING, enable as u8);
+        self
+    }
+
+    /// Brings the bridge up once created
+    pub fn up(mut self) -> Self {
+        self.link = self.link.up();
+        self
+    }
+
+    pub fn mtu(mut self, mtu: u32) -> Self {
+        self.link = self.link.mtu(mtu);
+        self
+    }
+
+    pub fn hw_addr(mut self, addr: MacAddr) -> Self {
+        self.link = self.link.hw_addr(addr);
+        self
+    }
+
+    /// Converts into a `LinkSpec` for `Links::create_link`
+    pub fn into_link_spec(self) -> LinkSpec {
+        self.link.info_data(self.data)
+    }
+}
+
+/// Options of an existing bridge, `None` for those
+/// the kernel did not report
+#[derive(Clone,Debug,Default,PartialEq,Eq)]
+pub struct BridgeInfo {
+    stp_state: Option<bool>,
+    forward_delay: Option<Duration>,
+    ageing_time: Option<Duration>,
+    vlan_filtering: Option<bool>,
+    default_pvid: Option<u16>,
+    mcast_snooping: Option<bool>,
+}
+
+impl BridgeInfo {
+    /// Decodes bridge options, `None` if `link` is not a bridge
+    pub fn from_link(link: &Link) -> Result<Option<Self>> {
+        if try!(link.view().get_kind()) != Some(LinkType::Bridge.kind()) {
+            return Ok(None);
+        }
+        match link.get_info_data() {
+            Some(data) => BridgeInfo::from_info_data(data).map(Some),
+            None => Err(Error::Malformed("bridge link without IFLA_INFO_DATA")),
+        }
+    }
+
+    /// Decodes attributes nested in IFLA_INFO_DATA
+    pub fn from_info_data(data: NlaIterator) -> Result<Self> {
+        let mut info = BridgeInfo::default();
+        for nla in data {
+            match nla.kind() {
+                IFLA_BR_STP_STATE => info.stp_state = Some(try!(nla.get::<u32>()) != 0),
+                IFLA_BR_FORWARD_DELAY => info.forward_delay = Some(from_ticks(try!(nla.get()))),
+                IFLA_BR_AGEING_TIME => info.ageing_time = Some(from_ticks(try!(nla.get()))),
+                IFLA_BR_VLAN_FILTERING => info.vlan_filtering = Some(try!(nla.get::<u8>()) != 0),
+                IFLA_BR_VLAN_DEFAULT_PVID => info.default_pvid = Some(try!(nla.get())),
+                IFLA_BR_MCAST_SNOOPING => info.mcast_snooping = Some(try!(nla.get::<u8>()) != 0),
+                _ => {},
+            }
+        }
+        Ok(info)
+    }
+
+    pub fn get_stp_state(&self) -> Option<bool> {
+        self.stp_state
+    }
+
+    pub fn get_forward_delay(&self) -> Option<Duration> {
+        self.forward_delay
+    }
+
+    pub fn get_ageing_time(&self) -> Option<Duration> {
+        self.ageing_time
+    }
+
+    pub fn get_vlan_filtering(&self) -> Option<bool> {
+        self.vlan_filtering
+    }
+
+    pub fn get_default_pvid(&self) -> Option<u16> {
+        self.default_pvid
+    }
+
+    pub fn get_mcast_snooping(&self) -> Option<bool> {
+        self.mcast_snooping
+    }
+}
+
+/// STP state of a bridge port
+#[derive(Copy,Clone,Debug,PartialEq,Eq)]
+pub enum BridgePortState {
+    Disabled = 0,
+    Listening = 1,
+    Learning = 2,
+    Forwarding = 3,
+    Blocking = 4,
+}
+
+impl BridgePortState {
+    pub fn from_u8(val: u8) -> Result<Self> {
+        match val {
+            0 => Ok(BridgePortState::Disabled),
+            1 => Ok(BridgePortState::Listening),
+            2 => Ok(BridgePortState::Learning),
+            3 => Ok(BridgePortState::Forwarding),
+            4 => Ok(BridgePortState::Blocking),
+            _ => Err(Error::Malformed("unknown bridge port state")),
+        }
+    }
+}
+
+/// Settings of a bridge port, `None` for those the kernel did not report
+#[derive(Clone,Debug,Default,PartialEq,Eq)]
+pub struct BridgePortInfo {
+    state: Option<BridgePortState>,
+    priority: Option<u16>,
+    cost: Option<u32>,
+    guard: Option<bool>,
+    learning: Option<bool>,
+    unicast_flood: Option<bool>,
+    proxyarp: Option<bool>,
+    isolated: Option<bool>,
+    neigh_suppress: Option<bool>,
+}
+
+impl BridgePortInfo {
+    /// Decodes port settings, `None` if `link` is not a bridge port
+    ///
+    /// Both AF_BRIDGE messages carrying IFLA_PROTINFO and plain link
+    /// messages carrying IFLA_INFO_SLAVE_DATA are understood.
+    pub fn from_link(link: &Link) -> Result<Option<Self>> {
+        let view = link.view();
+        if view.get_family() == AF_BRIDGE {
+            if let Some(protinfo) = view.attrs().find_kind(IFLA_PROTINFO) {
+                return BridgePortInfo::from_attrs(protinfo.nested()).map(Some);
+            }
+        }
+        if try!(view.get_slave_kind()) != Some(LinkType::Bridge.kind()) {
+            return Ok(None);
+        }
+        match view.get_slave_data() {
+            Some(data) => BridgePortInfo::from_attrs(data).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Decodes IFLA_BRPORT_* attributes
+    pub fn from_attrs(attrs: NlaIterator) -> Result<Self> {
+        let mut info = BridgePortInfo::default();
+        for nla in attrs {
+            match nla.kind() {
+                IFLA_BRPORT_STATE => info.state = Some(try!(BridgePortState::from_u8(try!(nla.get())))),
+                IFLA_BRPORT_PRIORITY => info.priority = Some(try!(nla.get())),
+                IFLA_BRPORT_COST => info.cost = Some(try!(nla.get())),
+                IFLA_BRPORT_GUARD => info.guard = Some(try!(nla.get::<u8>()) != 0),
+                IFLA_BRPORT_LEARNING => info.learning = Some(try!(nla.get::<u8>()) != 0),
+                IFLA_BRPORT_UNICAST_FLOOD => info.unicast_flood = Some(try!(nla.get::<u8>()) != 0),
+                IFLA_BRPORT_PROXYARP => info.proxyarp = Some(try!(nla.get::<u8>()) != 0),
+                IFLA_BRPORT_ISOLATED => info.isolated = Some(try!(nla.get::<u8>()) != 0),
+                IFLA_BRPORT_NEIGH_SUPPRESS => info.neigh_suppress = Some(try!(nla.get::<u8>()) != 0),
+                _ => {},
+            }
+        }
+        Ok(info)
+    }
+
+    pub fn get_state(&self) -> Option<BridgePortState> {
+        self.state
+    }
+
+    pub fn get_priority(&self) -> Option<u16> {
+        self.priority
+    }
+
+    pub fn get_cost(&self) -> Option<u32> {
+        self.cost
+    }
+
+    /// Whether BPDUs received on the port are dropped
+    pub fn get_guard(&self) -> Option<bool> {
+        self.guard
+    }
+
+    pub fn get_learning(&self) -> Option<bool> {
+        self.learning
+    }
+
+    /// Whether unknown unicast is flooded to the port
+    pub fn get_unicast_flood(&self) -> Option<bool> {
+        self.unicast_flood
+    }
+
+    pub fn get_proxyarp(&self) -> Option<bool> {
+        self.proxyarp
+    }
+
+    /// Whether the port only talks to non-isolated ports
+    pub fn get_isolated(&self) -> Option<bool> {
+        self.isolated
+    }
+
+    pub fn get_neigh_suppress(&self) -> Option<bool> {
+        self.neigh_suppress
+    }
+}
+
+/// Changes applied to a bridge port, see `Bridges::set_bridge_port`
+#[derive(Default)]
+pub struct BridgePortChange {
+    attrs: NlaBuilder,
+}
+
+impl BridgePortChange {
+    pub fn new() -> Self {
+        BridgePortChange { attrs: NlaBuilder::new() }
+    }
+
+    pub fn state(self, state: BridgePortState) -> Self {
+        self.nla(IFLA_BRPORT_STATE, state as u8)
+    }
+
+    pub fn priority(self, priority: u16) -> Self {
+        self.nla(IFLA_BRPORT_PRIORITY, priority)
+    }
+
+    pub fn cost(self, cost: u32) -> Self {
+        self.nla(IFLA_BRPORT_COST, cost)
+    }
+
+    pub fn guard(self, enable: bool) -> Self {
+        self.nla(IFLA_BRPORT_GUARD, enable as u8)
+    }
+
+    pub fn learning(self, enable: bool) -> Self {
+        self.nla(IFLA_BRPORT_LEARNING, enable as u8)
+    }
+
+    pub fn unicast_flood(self, enable: bool) -> Self {
+        self.nla(IFLA_BRPORT_UNICAST_FLOOD, enable as u8)
+    }
+
+    pub fn proxyarp(self, enable: bool) -> Self {
+        self.nla(IFLA_BRPORT_PROXYARP, enable as u8)
+    }
+
+    pub fn isolated(self, enable: bool) -> Self {
+        self.nla(IFLA_BRPORT_ISOLATED, enable as u8)
+    }
+
+    pub fn neigh_suppress(self, enable: bool) -> Self {
+        self.nla(IFLA_BRPORT_NEIGH_SUPPRESS, enable as u8)
+    }
+
+    /// Appends IFLA_BRPORT_* attribute `kind` with value `value`
+    pub fn nla<T: Nla>(mut self, kind: u16, value: T) -> Self {
+        self.attrs = self.attrs.append(kind, value);
+        self
+    }
+}
+
+/// VLAN or range of VLANs of a bridge port
+#[derive(Copy,Clone,Debug,PartialEq,Eq)]
+pub struct BridgeVlan {
+    start: u16,
+    end: u16,
+    flags: BridgeVlanFlags,
+}
+
+impl BridgeVlan {
+    /// Single VLAN `vid`
+    pub fn new(vid: u16) -> Self {
+        BridgeVlan::range(vid, vid)
+    }
+
+    /// VLANs `start` to `end` inclusive
+    pub fn range(start: u16, end: u16) -> Self {
+        BridgeVlan {
+            start: start,
+            end: end,
+            flags: BridgeVlanFlags::empty(),
+        }
+    }
+
+    /// Assigns untagged ingress frames to the VLAN, not allowed
+    /// for ranges, requests with those fail with `Error::Malformed`
+    pub fn pvid(mut self) -> Self {
+        self.flags.insert(BRIDGE_VLAN_INFO_PVID);
+        self
+    }
+
+    /// Sends frames of the VLAN untagged
+    pub fn untagged(mut self) -> Self {
+        self.flags.insert(BRIDGE_VLAN_INFO_UNTAGGED);
+        self
+    }
+
+    pub fn get_start(&self) -> u16 {
+        self.start
+    }
+
+    pub fn get_end(&self) -> u16 {
+        self.end
+    }
+
+    /// Flags without the range markers
+    pub fn get_flags(&self) -> BridgeVlanFlags {
+        self.flags
+    }
+
+    pub fn is_pvid(&self) -> bool {
+        self.flags.contains(BRIDGE_VLAN_INFO_PVID)
+    }
+
+    pub fn is_untagged(&self) -> bool {
+        self.flags.contains(BRIDGE_VLAN_INFO_UNTAGGED)
+    }
+
+    /// Appends IFLA_BRIDGE_VLAN_INFO entries, two for a range
+    fn emit(&self, attrs: NlaBuilder) -> NlaBuilder {
+        if self.start == self.end {
+            return attrs.append(IFLA_BRIDGE_VLAN_INFO, vlan_info(self.flags, self.start));
+        }
+        attrs.append(IFLA_BRIDGE_VLAN_INFO, vlan_info(self.flags | BRIDGE_VLAN_INFO_RANGE_BEGIN, self.start))
+            .append(IFLA_BRIDGE_VLAN_INFO, vlan_info(self.flags | BRIDGE_VLAN_INFO_RANGE_END, self.end))
+    }
+
+    /// Decodes IFLA_BRIDGE_VLAN_INFO entries of IFLA_AF_SPEC,
+    /// joining range markers
+    fn from_af_spec(attrs: NlaIterator) -> Result<Vec<Self>> {
+        let range = BRIDGE_VLAN_INFO_RANGE_BEGIN | BRIDGE_VLAN_INFO_RANGE_END;
+        let mut vlans = vec![];
+        let mut begin = None;
+        for nla in attrs.filter(|nla| nla.kind() == IFLA_BRIDGE_VLAN_INFO) {
+            let value = nla.value();
+            if value.len() != 4 {
+                return Err(Error::Malformed("attribute length"));
+            }
+            let flags = BridgeVlanFlags::from_bits_truncate(NativeEndian::read_u16(&value[..2]));
+            let vid = NativeEndian::read_u16(&value[2..]);
+            if flags.contains(BRIDGE_VLAN_INFO_RANGE_BEGIN) {
+                begin = Some(vid);
+            } else if flags.contains(BRIDGE_VLAN_INFO_RANGE_END) {
+                match begin.take() {
+                    Some(start) => vlans.push(BridgeVlan { start: start, end: vid, flags: flags - range }),
+                    None => return Err(Error::Malformed("VLAN range end without begin")),
+                }
+            } else {
+                vlans.push(BridgeVlan { start: vid, end: vid, flags: flags });
+            }
+        }
+        Ok(vlans)
+    }
+}
+
+fn vlan_info(flags: BridgeVlanFlags, vid: u16) -> Vec<u8> {
+    let mut buf = vec![0; 4];
+    NativeEndian::write_u16(&mut buf[..2], flags.bits());
+    NativeEndian::write_u16(&mut buf[2..], vid);
+    buf
+}
+
+/// VLAN membership of a bridge port, see `Bridges::snapshot_bridge_vlans`
+#[derive(Debug)]
+pub struct BridgePortVlans {
+    link: Link,
+    vlans: Vec<BridgeVlan>,
+}
+
+impl BridgePortVlans {
+    /// Decodes an AF_BRIDGE link message
+    pub fn from_link(link: Link) -> Result<Self> {
+        if link.get_family() != AF_BRIDGE {
+            return Err(Error::UnsupportedFamily(link.get_family()));
+        }
+        let vlans = match link.view().attrs().find_kind(IFLA_AF_SPEC) {
+            Some(af_spec) => try!(BridgeVlan::from_af_spec(af_spec.nested())),
+            None => vec![],
+        };
+        Ok(BridgePortVlans { link: link, vlans: vlans })
+    }
+
+    /// The port as reported in the AF_BRIDGE dump, see `Link::get_bridge_port`
+    pub fn get_link(&self) -> &Link {
+        &self.link
+    }
+
+    pub fn get_index(&self) -> u32 {
+        self.link.get_index()
+    }
+
+    pub fn get_vlans(&self) -> &[BridgeVlan] {
+        &self.vlans
+    }
+}
+
+/// Bridge port operations trait
+pub trait Bridges {
+    /// make `port` a port of `bridge`
+    fn enslave_link(&mut self, bridge: &Link, port: &Link) -> Result<()>;
+    /// remove `port` from its bridge
+    fn release_link(&mut self, port: &Link) -> Result<()>;
+    /// apply `change` to bridge port `port`
+    fn set_bridge_port(&mut self, port: &Link, change: BridgePortChange) -> Result<()>;
+    /// returns VLAN membership of all bridge ports, see `DumpPolicy`
+    fn snapshot_bridge_vlans(&mut self, policy: DumpPolicy) -> Result<Vec<BridgePortVlans>>;
+    /// add `vlan` to `port`, which may also be the bridge itself
+    fn add_bridge_vlan(&mut self, port: &Link, vlan: BridgeVlan) -> Result<()>;
+    /// remove `vlan` from `port`, which may also be the bridge itself
+    fn del_bridge_vlan(&mut self, port: &Link, vlan: BridgeVlan) -> Result<()>;
+}
+
+impl Bridges for NetlinkConnection {
+    fn enslave_link(&mut self, bridge: &Link, port: &Link) -> Result<()> {
+        self.set_link(port, LinkChange::new().master(bridge.get_index()))
+    }
+
+    fn release_link(&mut self, port: &Link) -> Result<()> {
+        self.set_link(port, LinkChange::new().master(0))
+    }
+
+    fn set_bridge_port(&mut self, port: &Link, change: BridgePortChange) -> Result<()> {
+        let req = set_bridge_port_request(port.get_index(), change);
+        let reader = try!(self.send(req));
+        reader.read_to_end()
+    }
+
+    fn snapshot_bridge_vlans(&mut self, policy: DumpPolicy) -> Result<Vec<BridgePortVlans>> {
+        let pkts = try!(self.dump(&dump_bridge_vlans_request(), policy));
+        pkts.into_iter()
+            .filter(|pkt| pkt.get_kind() == RTM_NEWLINK)
+            .map(|pkt| Link::from_packet(pkt).and_then(BridgePortVlans::from_link))
+            .collect()
+    }
+
+    fn add_bridge_vlan(&mut self, port: &Link, vlan: BridgeVlan) -> Result<()> {
+        let req = try!(bridge_vlan_request(RTM_SETLINK, port, vlan));
+        let reader = try!(self.send(req));
+        reader.read_to_end()
+    }
+
+    fn del_bridge_vlan(&mut self, port: &Link, vlan: BridgeVlan) -> Result<()> {
+        let req = try!(bridge_vlan_request(RTM_DELLINK, port, vlan));
+        let reader = try!(self.send(req));
+        reader.read_to_end()
+    }
+}
+
+/// Builds an AF_BRIDGE message for link `index` followed by `attrs`
+fn bridge_ifinfo(index: u32, attrs: NlaBuilder) -> IfInfoPacket<'static> {
+    let mut data = vec![0; MutableIfInfoPacket::minimum_packet_size()];
+    {
+        let mut ifinfo = MutableIfInfoPacket::new(&mut data).unwrap();
+        ifinfo.set_family(AF_BRIDGE);
+        ifinfo.set_index(index);
+    }
+    data.extend_from_slice(&attrs.build());
+    IfInfoPacket::owned(data).unwrap()
+}
+
+fn dump_bridge_vlans_request() -> NetlinkPacket<'static> {
+    let attrs = NlaBuilder::new().append(IFLA_EXT_MASK, RTEXT_FILTER_BRVLAN);
+    NetlinkRequestBuilder::new(RTM_GETLINK, NLM_F_DUMP)
+        .append(bridge_ifinfo(0, attrs))
+        .build()
+}
+
+fn bridge_vlan_request(kind: u16, port: &Link, vlan: BridgeVlan) -> Result<NetlinkPacket<'static>> {
+    if vlan.is_pvid() && vlan.start != vlan.end {
+        return Err(Error::Malformed("PVID set on a VLAN range"));
+    }
+    let mut af_spec = NlaBuilder::new();
+    // VLANs of the bridge device itself are not configured through a master
+    if try!(port.view().get_kind()) == Some(LinkType::Bridge.kind()) {
+        af_spec = af_spec.append(IFLA_BRIDGE_FLAGS, BRIDGE_FLAGS_SELF);
+    }
+    af_spec = vlan.emit(af_spec);
+    let attrs = NlaBuilder::new().nested(IFLA_AF_SPEC, af_spec);
+    Ok(NetlinkRequestBuilder::new(kind, NLM_F_ACK)
+        .append(bridge_ifinfo(port.get_index(), attrs))
+        .build())
+}
+
+fn set_bridge_port_request(index: u32, change: BridgePortChange) -> NetlinkPacket<'static> {
+    let attrs = NlaBuilder::new().nested(IFLA_PROTINFO, change.attrs);
+    NetlinkRequestBuilder::new(RTM_SETLINK, NLM_F_ACK)
+        .append(bridge_ifinfo(index, attrs))
+        .build()
+}
+
+#[test]
+fn bridge_ticks() {
+    assert_eq!(to_ticks(Duration::from_millis(1500)), 150);
+    assert_eq!(from_ticks(30000), Duration::from_secs(300));
+    assert_eq!(from_ticks(to_ticks(Duration::from_millis(2510))), Duration::from_millis(2510));
+}
+
+#[test]
+fn bridge_info_roundtrip() {
+    let spec = BridgeSpec::new("br0")
+        .stp_state(true)
+        .forward_delay(Duration::from_secs(4))
+        .vlan_filtering(true)
+        .default_pvid(10)
+        .mcast_snooping(false)
+        .into_link_spec();
+    let data = spec.get_info_data().unwrap().clone().build();
+    // the kernel rejects attributes of the wrong width
+    let widths = NlaIterator::new(&data).map(|nla| (nla.kind(), nla.value().len())).collect::<Vec<_>>();
+    assert_eq!(widths, vec![(IFLA_BR_STP_STATE, 4), (IFLA_BR_FORWARD_DELAY, 4),
+                            (IFLA_BR_VLAN_FILTERING, 1), (IFLA_BR_VLAN_DEFAULT_PVID, 2),
+                            (IFLA_BR_MCAST_SNOOPING, 1)]);
+    let info = BridgeInfo::from_info_data(NlaIterator::new(&data)).unwrap();
+    assert_eq!(info, BridgeInfo {
+        stp_state: Some(true),
+        forward_delay: Some(Duration::from_secs(4)),
+        ageing_time: None,
+        vlan_filtering: Some(true),
+        default_pvid: Some(10),
+        mcast_snooping: Some(false),
+    });
+}
+
+#[test]
+fn bridge_vlan_ranges() {
+    let vlans = [BridgeVlan::new(1).pvid().untagged(), BridgeVlan::range(10, 20), BridgeVlan::new(30)];
+    let attrs = vlans.iter().fold(NlaBuilder::new(), |attrs, vlan| vlan.emit(attrs)).build();
+    assert_eq!(NlaIterator::new(&attrs).count(), 4);
+    assert_eq!(BridgeVlan::from_af_spec(NlaIterator::new(&attrs)).unwrap(), vlans);
+}
+
+#[test]
+fn bridge_vlan_requests() {
+    use packet::netlink::NLM_F_REQUEST;
+    use packet::route::message;
+    use packet::route::link::{IFLA_LINKINFO,IFLA_INFO_KIND};
+    use pnet::packet::Packet;
+
+    let req = dump_bridge_vlans_request();
+    assert_eq!((req.get_kind(), req.get_flags()), (RTM_GETLINK, NLM_F_REQUEST | NLM_F_DUMP));
+    let ifinfo = IfInfoPacket::new(req.payload()).unwrap();
+    assert_eq!(ifinfo.get_family(), AF_BRIDGE);
+    assert_eq!(NlaIterator::new(ifinfo.payload()).get::<u32>(IFLA_EXT_MASK).unwrap(), Some(RTEXT_FILTER_BRVLAN));
+
+    let link = |index, attrs| Link::from_packet(message(RTM_NEWLINK, bridge_ifinfo(index, attrs))).unwrap();
+    let bridge = link(3, NlaBuilder::new().nested(IFLA_LINKINFO, NlaBuilder::new().append(IFLA_INFO_KIND, "bridge")));
+    let port = link(5, NlaBuilder::new());
+    let decode = |req: NetlinkPacket| {
+        assert_eq!(req.get_flags(), NLM_F_REQUEST | NLM_F_ACK);
+        let ifinfo = IfInfoPacket::new(req.payload()).unwrap();
+        let af_spec = NlaIterator::new(ifinfo.payload()).find_kind(IFLA_AF_SPEC).unwrap();
+        (ifinfo.get_index(),
+         af_spec.nested().get::<u16>(IFLA_BRIDGE_FLAGS).unwrap(),
+         BridgeVlan::from_af_spec(af_spec.nested()).unwrap())
+    };
+
+    // only the bridge's own VLANs are flagged with SELF
+    let vlan = BridgeVlan::new(10).pvid().untagged();
+    let req = bridge_vlan_request(RTM_SETLINK, &bridge, vlan).unwrap();
+    assert_eq!(req.get_kind(), RTM_SETLINK);
+    assert_eq!(decode(req), (3, Some(BRIDGE_FLAGS_SELF), vec![vlan]));
+    let req = bridge_vlan_request(RTM_DELLINK, &port, vlan).unwrap();
+    assert_eq!(req.get_kind(), RTM_DELLINK);
+    assert_eq!(decode(req), (5, None, vec![vlan]));
+
+    let vlans = BridgeVlan::range(10, 20).untagged();
+    assert_eq!(decode(bridge_vlan_request(RTM_SETLINK, &port, vlans).unwrap()), (5, None, vec![vlans]));
+    match bridge_vlan_request(RTM_SETLINK, &port, vlans.pvid()) {
+        Err(Error::Malformed(_)) => {},
+        res => panic!("unexpected {:?}", res.map(|_| ())),
+    }
+}
+
+#[test]
+fn bridge_port_roundtrip() {
+    use pnet::packet::Packet;
+
+    let change = BridgePortChange::new()
+        .state(BridgePortState::Blocking)
+        .priority(7)
+        .cost(100)
+        .learning(false)
+        .isolated(true);
+    let req = set_bridge_port_request(5, change);
+    assert_eq!(req.get_kind(), RTM_SETLINK);
+    let ifinfo = IfInfoPacket::new(req.payload()).unwrap();
+    assert_eq!((ifinfo.get_family(), ifinfo.get_index()), (AF_BRIDGE, 5));
+    let protinfo = NlaIterator::new(ifinfo.payload()).find_kind(IFLA_PROTINFO).unwrap();
+    assert!(protinfo.is_nested());
+    let info = BridgePortInfo::from_attrs(protinfo.nested()).unwrap();
+    assert_eq!(info.get_state(), Some(BridgePortState::Blocking));
+    assert_eq!((info.get_priority(), info.get_cost()), (Some(7), Some(100)));
+    assert_eq!((info.get_learning(), info.get_isolated()), (Some(false), Some(true)));
+    assert_eq!(info.get_guard(), None);
+
+    // the kernel reports the same layout in AF_BRIDGE RTM_NEWLINK
+    let link = Link::from_packet(::packet::route::message(RTM_NEWLINK, ifinfo)).unwrap();
+    assert_eq!(BridgePortInfo::from_link(&link).unwrap(), Some(info));
+}
@@ -0,0 +1,486 @@
+//! Synchronized in-memory copy of links, addresses, routes and neighbours
+//!
+//! The cache subscribes to notifications first and dumps the current
+//! state afterwards, so no change is missed. Pending notifications are
+//! applied by `process`, call it whenever the cache's descriptor
+//! becomes readable.
+//!
+//! # Example
+//! ```ignore
+//! use pnetlink::packet::route::cache::Cache;
+//!
+//! let mut cache = Cache::new()?;
+//! cache.on_change(|event| println!("{:?}", event));
+//! loop {
+//!     // wait for cache.as_raw_fd() to become readable
+//!     cache.process()?;
+//!     println!("{:?}", cache.index_by_name("eth0"));
+//! }
+//! ```
+use packet::route::RtnlGroup;
+use packet::route::link::{Link,Links};
+use packet::route::addr::{Addr,Addresses,IpAddr};
+use packet::route::route::Route;
+use packet::route::neighbour::{Neighbour,Neighbours};
+use packet::route::bridge::AF_BRIDGE;
+use packet::route::monitor::{Monitor,MonitorBuilder,Event};
+use packet::netlink::{NetlinkConnection,DumpPolicy};
+use error::Result;
+use pnet::util::MacAddr;
+use std::collections::HashMap;
+use std::collections::hash_map::Values;
+use std::os::unix::io::{AsRawFd,RawFd};
+
+/// Number of times an interrupted dump is restarted
+const DUMP_RETRIES: usize = 10;
+
+/// Subscribed groups
+const GROUPS: &'static [RtnlGroup] = &[
+    RtnlGroup::Link,
+    RtnlGroup::Ipv4Ifaddr,
+    RtnlGroup::Ipv6Ifaddr,
+    RtnlGroup::Ipv4Route,
+    RtnlGroup::Ipv6Route,
+    RtnlGroup::Neigh,
+];
+
+#[derive(Default)]
+struct State {
+    links: HashMap<u32, Link>,
+    addrs: HashMap<AddrKey, Addr>,
+    routes: HashMap<RouteKey, Route>,
+    neighbours: HashMap<NeighbourKey, Neighbour>,
+}
+
+impl State {
+    fn dump(conn: &mut NetlinkConnection) -> Result<Self> {
+        let policy = DumpPolicy::Retry(DUMP_RETRIES);
+        let mut state = State::default();
+        for link in try!(conn.snapshot_links(policy)) {
+            state.links.insert(link.get_index(), link);
+        }
+        for addr in try!(conn.snapshot_addrs(None, policy)) {
+            state.addrs.insert(addr_key(&addr), addr);
+        }
+        for route in try!(Route::snapshot_routes(conn, policy)) {
+            state.routes.insert(route_key(&route), route);
+        }
+        for neighbour in try!(conn.snapshot_neighbours(None, policy)) {
+            state.neighbours.insert(neighbour_key(&neighbour), neighbour);
+        }
+        Ok(state)
+    }
+
+    fn apply(&mut self, event: Event) {
+        match event {
+            Event::NewLink(link) => {
+                self.links.insert(link.get_index(), link);
+            },
+            Event::DelLink(link) => {
+                let index = link.get_index();
+                self.links.remove(&index);
+                // the kernel does not always notify about these
+                self.addrs.retain(|_, addr| addr.get_link_index() != index);
+                self.routes.retain(|_, route| route.view().get_oif().unwrap_or(None) != Some(index));
+                self.neighbours.retain(|_, neighbour| neighbour.get_ifindex() != index);
+            },
+            Event::NewAddr(addr) => {
+                self.addrs.insert(addr_key(&addr), addr);
+            },
+            Event::DelAddr(addr) => {
+                self.addrs.remove(&addr_key(&addr));
+            },
+            Event::NewRoute(route) => {
+                self.routes.insert(route_key(&route), route);
+            },
+            Event::DelRoute(route) => {
+                self.routes.remove(&route_key(&route));
+            },
+            Event::NewNeigh(neighbour) => {
+                self.neighbours.insert(neighbour_key(&neighbour), neighbour);
+            },
+            Event::DelNeigh(neighbour) => {
+                self.neighbours.remove(&neighbour_key(&neighbour));
+            },
+            _ => {},
+        }
+    }
+}
+
+type AddrKey = (u32, u8, u8, Option<IpAddr>);
+type RouteKey = (u8, u32, u8, Option<IpAddr>, u8, Option<u32>);
+type NeighbourKey = (u32, u8, Option<IpAddr>, Option<MacAddr>, Option<u16>);
+type Callback = Box<FnMut(&Event)>;
+
+fn addr_key(addr: &Addr) -> AddrKey {
+    let ip = match addr.get_local_ip().unwrap_or(None) {
+        Some(ip) => Some(ip),
+        None => addr.get_ip().unwrap_or(None),
+    };
+    (addr.get_link_index(), addr.get_family(), addr.get_prefix_len(), ip)
+}
+
+fn route_key(route: &Route) -> RouteKey {
+    let route = route.view();
+    (route.get_family(), route.get_table().unwrap_or(0), route.get_dst_len(),
+     route.get_destination().unwrap_or(None), route.get_tos(), route.get_priority().unwrap_or(None))
+}
+
+/// FDB entries have no destination, they are told apart by
+/// link layer address and VLAN instead
+fn neighbour_key(neighbour: &Neighbour) -> NeighbourKey {
+    let (index, family) = (neighbour.get_ifindex(), neighbour.get_family());
+    if family == AF_BRIDGE {
+        (index, family, None, neighbour.get_ll_addr(), neighbour.get_vlan_id().unwrap_or(None))
+    } else {
+        (index, family, neighbour.get_destination().unwrap_or(None), None, None)
+    }
+}
+
+/// Cache of links, addresses, routes and neighbours kept
+/// up to date by notifications
+///
+/// After a notification overflow the state is replaced by a fresh
+/// dump, `Event::Resynced` is reported to callbacks once it is done.
+pub struct Cache {
+    monitor: Monitor,
+    state: State,
+    resync: Option<State>,
+    callbacks: Vec<Callback>,
+}
+
+impl Cache {
+    /// Subscribes to notifications and dumps the current state
+    pub fn new() -> Result<Self> {
+        let mut monitor = try!(MonitorBuilder::new()
+            .groups(GROUPS)
+            .resync(DumpPolicy::Retry(DUMP_RETRIES))
+            .build());
+        try!(monitor.set_nonblocking(true));
+        let mut conn = try!(NetlinkConnection::new());
+        let state = try!(State::dump(&mut conn));
+        Ok(Cache {
+            monitor: monitor,
+            state: state,
+            resync: None,
+            callbacks: vec![],
+        })
+    }
+
+    /// Registers `callback` called for every notification
+    /// before it is applied
+    pub fn on_change<F: FnMut(&Event) + 'static>(&mut self, callback: F) {
+        self.callbacks.push(Box::new(callback));
+    }
+
+    /// Applies all queued notifications without blocking,
+    /// returns the number of processed events
+    pub fn process(&mut self) -> Result<usize> {
+        let mut count = 0;
+        while let Some(event) = try!(self.monitor.try_next()) {
+            self.handle(event);
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    fn handle(&mut self, event: Event) {
+        for callback in &mut self.callbacks {
+            callback(&event);
+        }
+        match event {
+            Event::Overflow => self.resync = Some(State::default()),
+            Event::Resynced => {
+                if let Some(state) = self.resync.take() {
+                    self.state = state;
+                }
+            },
+            event => match self.resync {
+                Some(ref mut state) => state.apply(event),
+                None => self.state.apply(event),
+            },
+        }
+    }
+
+    /// Replaces the state with a fresh dump
+    pub fn resync(&mut self) -> Result<()> {
+        let mut conn = try!(NetlinkConnection::new());
+        let state = try!(State::dump(&mut conn));
+        self.state = state;
+        self.resync = None;
+        Ok(())
+    }
+
+    /// All links
+    pub fn links<'a>(&'a self) -> Values<'a, u32, Link> {
+        self.state.links.values()
+    }
+
+    /// Link with index `index`
+    pub fn link_by_index(&self, index: u32) -> Option<&Link> {
+        self.state.links.get(&index)
+    }
+
+    /// Link named `name`
+    pub fn link_by_name(&self, name: &str) -> Option<&Link> {
+        self.state.links.values().find(|link| {
+            link.view().get_name().unwrap_or(None) == Some(name)
+        })
+    }
+
+    /// Index of link named `name`
+    pub fn index_by_name(&self, name: &str) -> Option<u32> {
+        self.link_by_name(name).map(|link| link.get_index())
+    }
+
+    /// All addresses
+    pub fn addrs<'a>(&'a self) -> Values<'a, AddrKey, Addr> {
+        self.state.addrs.values()
+    }
+
+    /// Addresses of link with index `index`
+    pub fn link_addrs(&self, index: u32) -> Vec<&Addr> {
+        self.state.addrs.values().filter(|addr| addr.get_link_index() == index).collect()
+    }
+
+    /// All routes
+    pub fn routes<'a>(&'a self) -> Values<'a, RouteKey, Route> {
+        self.state.routes.values()
+    }
+
+    /// Default route of `family` in the main table with the lowest metric
+    pub fn default_route(&self, family: u8) -> Option<&Route> {
+        use packet::route::route::RT_TABLE_MAIN;
+
+        self.state.routes.values()
+            .filter(|route| {
+                let route = route.view();
+                route.get_family() == family && route.get_dst_len() == 0 &&
+                    route.get_table().ok() == Some(RT_TABLE_MAIN)
+            })
+            .min_by_key(|route| route.view().get_priority().unwrap_or(None).unwrap_or(0))
+    }
+
+    /// All neighbours
+    pub fn neighbours<'a>(&'a self) -> Values<'a, NeighbourKey, Neighbour> {
+        self.state.neighbours.values()
+    }
+
+    /// Neighbours on link with index `index`
+    pub fn link_neighbours(&self, index: u32) -> Vec<&Neighbour> {
+        self.state.neighbours.values().filter(|neighbour| neighbour.get_ifindex() == index).collect()
+    }
+}
+
+impl AsRawFd for Cache {
+    fn as_raw_fd(&self) -> RawFd {
+        self.monitor.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+fn event<P: ::pnet::packet::PacketSize + ::pnet::packet::Packet>(kind: u16, msg: P) -> Event {
+    Event::from_packet(::packet::route::message(kind, msg)).unwrap()
+}
+
+#[cfg(test)]
+fn link_event(kind: u16, index: u32) -> Event {
+    Event::from_packet(::packet::route::link_message(kind, index)).unwrap()
+}
+
+#[cfg(test)]
+fn addr_event(kind: u16, index: u32, local: [u8; 4], peer: [u8; 4]) -> Event {
+    use packet::route::MutableIfAddrPacket;
+    use packet::route::addr::{IFA_ADDRESS,IFA_LOCAL};
+    use packet::route::nla::NlaBuilder;
+    use std::net::Ipv4Addr;
+
+    let attrs = NlaBuilder::new()
+        .append(IFA_ADDRESS, Ipv4Addr::from(peer))
+        .append(IFA_LOCAL, Ipv4Addr::from(local))
+        .build();
+    let mut ifaddr = MutableIfAddrPacket::owned(vec![0; 8 + attrs.len()]).unwrap();
+    ifaddr.set_family(2);
+    ifaddr.set_prefix_len(24);
+    ifaddr.set_index(index);
+    ifaddr.set_payload(&attrs);
+    event(kind, ifaddr)
+}
+
+#[cfg(test)]
+fn route_event(kind: u16, dst: [u8; 4], oif: u32, priority: u32) -> Event {
+    use packet::route::MutableRtMsgPacket;
+    use packet::route::route::{RTA_DST,RTA_OIF,RTA_PRIORITY,RTA_TABLE,RT_TABLE_MAIN};
+    use packet::route::nla::NlaBuilder;
+    use std::net::Ipv4Addr;
+
+    let attrs = NlaBuilder::new()
+        .append(RTA_TABLE, RT_TABLE_MAIN)
+        .append(RTA_DST, Ipv4Addr::from(dst))
+        .append(RTA_OIF, oif)
+        .append(RTA_PRIORITY, priority)
+        .build();
+    let mut rtmsg = MutableRtMsgPacket::owned(vec![0; 12 + attrs.len()]).unwrap();
+    rtmsg.set_rtm_family(2);
+    rtmsg.set_rtm_dst_len(24);
+    rtmsg.set_payload(&attrs);
+    event(kind, rtmsg)
+}
+
+#[cfg(test)]
+fn neighbour_event(kind: u16, index: u32, dst: [u8; 4]) -> Event {
+    use packet::route::MutableNeighbourDiscoveryPacket;
+    use packet::route::neighbour::NeighbourAttributes;
+    use packet::route::nla::NlaBuilder;
+    use std::net::Ipv4Addr;
+
+    let attrs = NlaBuilder::new()
+        .append(NeighbourAttributes::DST as u16, Ipv4Addr::from(dst))
+        .build();
+    let mut ndmsg = MutableNeighbourDiscoveryPacket::owned(vec![0; 12 + attrs.len()]).unwrap();
+    ndmsg.set_family(2);
+    ndmsg.set_ifindex(index);
+    ndmsg.set_payload(&attrs);
+    event(kind, ndmsg)
+}
+
+#[cfg(test)]
+fn fdb_event(kind: u16, index: u32, ll_addr: MacAddr, vlan: Option<u16>) -> Event {
+    use packet::route::MutableNeighbourDiscoveryPacket;
+    use packet::route::neighbour::NeighbourAttributes;
+    use packet::route::nla::NlaBuilder;
+
+    let attrs = NlaBuilder::new()
+        .append(NeighbourAttributes::LLADDR as u16, ll_addr)
+        .append_opt(NeighbourAttributes::VLAN as u16, vlan)
+        .build();
+    let mut ndmsg = MutableNeighbourDiscoveryPacket::owned(vec![0; 12 + attrs.len()]).unwrap();
+    ndmsg.set_family(AF_BRIDGE);
+    ndmsg.set_ifindex(index);
+    ndmsg.set_payload(&attrs);
+    event(kind, ndmsg)
+}
+
+#[test]
+fn apply_replaces_and_removes() {
+    use packet::route::link::{RTM_NEWLINK,RTM_DELLINK};
+    use packet::route::addr::{RTM_NEWADDR,RTM_DELADDR};
+    use packet::route::route::{RTM_NEWROUTE,RTM_DELROUTE};
+    use packet::route::neighbour::{RTM_NEWNEIGH,RTM_DELNEIGH};
+
+    let mut state = State::default();
+    state.apply(link_event(RTM_NEWLINK, 2));
+    state.apply(link_event(RTM_NEWLINK, 2));
+    assert_eq!(state.links.len(), 1);
+
+    // keyed by the local address, the peer may change
+    state.apply(addr_event(RTM_NEWADDR, 2, [10, 0, 0, 1], [10, 0, 0, 1]));
+    state.apply(addr_event(RTM_NEWADDR, 2, [10, 0, 0, 1], [10, 0, 0, 9]));
+    state.apply(addr_event(RTM_NEWADDR, 2, [10, 0, 0, 2], [10, 0, 0, 2]));
+    assert_eq!(state.addrs.len(), 2);
+    let key = (2, 2, 24, Some(IpAddr::V4([10, 0, 0, 1].into())));
+    assert_eq!(state.addrs[&key].get_ip().unwrap(), Some(IpAddr::V4([10, 0, 0, 9].into())));
+    state.apply(addr_event(RTM_DELADDR, 2, [10, 0, 0, 2], [10, 0, 0, 2]));
+    assert_eq!(state.addrs.len(), 1);
+
+    // output link is not part of the key, the metric is
+    state.apply(route_event(RTM_NEWROUTE, [10, 1, 0, 0], 2, 0));
+    state.apply(route_event(RTM_NEWROUTE, [10, 1, 0, 0], 3, 0));
+    state.apply(route_event(RTM_NEWROUTE, [10, 1, 0, 0], 2, 100));
+    assert_eq!(state.routes.len(), 2);
+    let key = (2, 254, 24, Some(IpAddr::V4([10, 1, 0, 0].into())), 0, Some(0));
+    assert_eq!(state.routes[&key].view().get_oif().unwrap(), Some(3));
+    state.apply(route_event(RTM_DELROUTE, [10, 1, 0, 0], 2, 100));
+    assert_eq!(state.routes.len(), 1);
+
+    state.apply(neighbour_event(RTM_NEWNEIGH, 2, [10, 0, 0, 5]));
+    state.apply(neighbour_event(RTM_NEWNEIGH, 2, [10, 0, 0, 5]));
+    state.apply(neighbour_event(RTM_NEWNEIGH, 3, [10, 0, 0, 5]));
+    assert_eq!(state.neighbours.len(), 2);
+    state.apply(neighbour_event(RTM_DELNEIGH, 3, [10, 0, 0, 5]));
+    assert_eq!(state.neighbours.len(), 1);
+
+    assert_eq!(state.addrs.keys().collect::<Vec<_>>(),
+               vec![&(2, 2, 24, Some(IpAddr::V4([10, 0, 0, 1].into())))]);
+    assert_eq!(state.routes.keys().collect::<Vec<_>>(),
+               vec![&(2, 254, 24, Some(IpAddr::V4([10, 1, 0, 0].into())), 0, Some(0))]);
+    assert_eq!(state.neighbours.keys().collect::<Vec<_>>(),
+               vec![&(2, 2, Some(IpAddr::V4([10, 0, 0, 5].into())), None, None)]);
+
+    state.apply(link_event(RTM_DELLINK, 2));
+    assert!(state.links.is_empty());
+}
+
+#[test]
+fn apply_del_link_cascades() {
+    use packet::route::link::{RTM_NEWLINK,RTM_DELLINK};
+    use packet::route::addr::RTM_NEWADDR;
+    use packet::route::route::RTM_NEWROUTE;
+    use packet::route::neighbour::RTM_NEWNEIGH;
+
+    let mut state = State::default();
+    for &index in &[2, 3] {
+        state.apply(link_event(RTM_NEWLINK, index));
+        state.apply(addr_event(RTM_NEWADDR, index, [10, 0, index as u8, 1], [10, 0, index as u8, 1]));
+        state.apply(route_event(RTM_NEWROUTE, [10, 0, index as u8, 0], index, 0));
+        state.apply(neighbour_event(RTM_NEWNEIGH, index, [10, 0, index as u8, 5]));
+    }
+    state.apply(link_event(RTM_DELLINK, 2));
+    assert_eq!(state.links.keys().collect::<Vec<_>>(), vec![&3]);
+    assert!(state.addrs.values().all(|addr| addr.get_link_index() == 3));
+    assert!(state.routes.values().all(|route| route.view().get_oif().unwrap() == Some(3)));
+    assert!(state.neighbours.values().all(|neighbour| neighbour.get_ifindex() == 3));
+    assert_eq!((state.addrs.len(), state.routes.len(), state.neighbours.len()), (1, 1, 1));
+}
+
+#[test]
+fn overflow_swaps_state_on_resync() {
+    use packet::route::link::RTM_NEWLINK;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    let mut cache = Cache {
+        monitor: Monitor::new(&[]).unwrap(),
+        state: State::default(),
+        resync: None,
+        callbacks: vec![],
+    };
+    let seen = Rc::new(Cell::new(0));
+    let counter = seen.clone();
+    cache.on_change(move |_| counter.set(counter.get() + 1));
+
+    cache.handle(link_event(RTM_NEWLINK, 2));
+    cache.handle(Event::Overflow);
+    // the old state stays visible until the resync is complete
+    cache.handle(link_event(RTM_NEWLINK, 3));
+    assert!(cache.link_by_index(2).is_some());
+    assert!(cache.link_by_index(3).is_none());
+    cache.handle(Event::Resynced);
+    assert!(cache.link_by_index(2).is_none());
+    assert!(cache.link_by_index(3).is_some());
+    assert!(cache.resync.is_none());
+    // Resynced without Overflow keeps the state
+    cache.handle(Event::Resynced);
+    assert!(cache.link_by_index(3).is_some());
+    assert_eq!(seen.get(), 5);
+}
+
+#[test]
+fn apply_fdb_entries_on_one_port() {
+    use packet::route::neighbour::{RTM_NEWNEIGH,RTM_DELNEIGH};
+
+    let mac = MacAddr::new(0x02, 0, 0, 0, 0, 1);
+    let other = MacAddr::new(0x02, 0, 0, 0, 0, 2);
+    let mut state = State::default();
+    state.apply(fdb_event(RTM_NEWNEIGH, 5, mac, None));
+    state.apply(fdb_event(RTM_NEWNEIGH, 5, other, None));
+    state.apply(fdb_event(RTM_NEWNEIGH, 5, mac, Some(10)));
+    state.apply(fdb_event(RTM_NEWNEIGH, 5, mac, None));
+    assert_eq!(state.neighbours.len(), 3);
+    assert!(state.neighbours.contains_key(&(5, AF_BRIDGE, None, Some(other), None)));
+    assert!(state.neighbours.contains_key(&(5, AF_BRIDGE, None, Some(mac), Some(10))));
+
+    state.apply(fdb_event(RTM_DELNEIGH, 5, mac, None));
+    assert_eq!(state.neighbours.len(), 2);
+    assert!(!state.neighbours.contains_key(&(5, AF_BRIDGE, None, Some(mac), None)));
+}
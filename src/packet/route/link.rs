@@ -8,27 +8,41 @@
 //! use pnetlink::packet::route::link::{Links,Link};
 //! use pnetlink::packet::route::addr::{Addresses,Addr};
 //!
-//! let mut conn = NetlinkConnection::new();
-//! let links = conn.iter_links().unwrap().collect::<Vec<_>>();
+//! let mut conn = NetlinkConnection::new().unwrap();
+//! let links = conn.iter_links().unwrap().collect::<Result<Vec<_>,_>>().unwrap();
 //! for link in links {
 //!    ...
 //! }
 //! ```
 
 
+use packet::route::nla::{Nla,NlaBuilder,NlaIterator,FromNla,emit_nla};
 use packet::route::{IfInfoPacket,MutableIfInfoPacket,RtAttrIterator,RtAttrPacket,MutableRtAttrPacket,RtAttrMtuPacket};
 use packet::netlink::{MutableNetlinkPacket,NetlinkPacket,NetlinkErrorPacket};
 use packet::netlink::{NLM_F_ACK,NLM_F_REQUEST,NLM_F_DUMP,NLM_F_MATCH,NLM_F_EXCL,NLM_F_CREATE};
 use packet::netlink::{NLMSG_NOOP,NLMSG_ERROR,NLMSG_DONE,NLMSG_OVERRUN};
 use packet::netlink::{NetlinkBufIterator,NetlinkReader,NetlinkRequestBuilder};
 use ::socket::{NetlinkSocket,NetlinkProtocol};
-use packet::netlink::NetlinkConnection;
+use packet::netlink::{NetlinkConnection,DumpPolicy,message_bytes};
+use error::{Error,Result};
 use pnet::packet::MutablePacket;
 use pnet::packet::Packet;
 use pnet::packet::PacketSize;
 use pnet::util::MacAddr;
+use packet::route::bridge::BridgePortInfo;
+use packet::route::veth::VethSpec;
+use netns::NetNs;
 use libc;
+use std::fs::File;
 use std::io::{Read,Write,self};
+use std::os::unix::io::AsRawFd;
+
+#[cfg(feature = "async")]
+use packet::async_netlink::{AsyncNetlinkConnection,NetlinkStream};
+#[cfg(feature = "async")]
+use futures::future::{self,BoxFuture,FutureExt,TryFutureExt};
+#[cfg(feature = "async")]
+use futures::stream::{BoxStream,StreamExt,TryStreamExt};
 
 /* rt message types */
 pub const RTM_NEWLINK: u16 = 16;
@@ -85,23 +99,39 @@ pub const IFLA_INFO_UNSPEC: u16 = 0;
 pub const IFLA_INFO_KIND: u16 = 1;
 pub const IFLA_INFO_DATA: u16 = 2;
 pub const IFLA_INFO_XSTATS: u16 = 3;
+pub const IFLA_INFO_SLAVE_KIND: u16 = 4;
+pub const IFLA_INFO_SLAVE_DATA: u16 = 5;
 
 /// Interface type
-/// NB: Only Generic, Ether and Loopback are currently defined
-#[derive(Debug,Copy,Clone)]
-#[repr(u16)]
+/// NB: Only Generic, Ether and Loopback are currently defined,
+/// anything else is kept as `Other`
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
 pub enum IfType {
     /* todo: more types, see if_link.h */
-    Generic = 0,
-    Ether = 1,
-    Loopback = 772,
+    Generic,
+    Ether,
+    Loopback,
+    Other(u16),
 }
 
 impl IfType {
     pub fn new(val: u16) -> Self {
-        use std::mem;
-        /* XXX */
-        unsafe { mem::transmute(val) }
+        match val {
+            0 => IfType::Generic,
+            1 => IfType::Ether,
+            772 => IfType::Loopback,
+            _ => IfType::Other(val),
+        }
+    }
+
+    /// ARPHRD_* value
+    pub fn value(&self) -> u16 {
+        match *self {
+            IfType::Generic => 0,
+            IfType::Ether => 1,
+            IfType::Loopback => 772,
+            IfType::Other(val) => val,
+        }
     }
 }
 
@@ -117,6 +147,22 @@ pub enum LinkType {
     Bridge
 }
 
+impl LinkType {
+    /// Kind name used in IFLA_INFO_KIND
+    pub fn kind(&self) -> &'static str {
+        match *self {
+            LinkType::Vlan => "vlan",
+            LinkType::Veth => "veth",
+            LinkType::Vcan => "vcan",
+            LinkType::Dummy => "dummy",
+            LinkType::Ifb => "ifb",
+            LinkType::MacVlan => "macvlan",
+            LinkType::Can => "can",
+            LinkType::Bridge => "bridge",
+        }
+    }
+}
+
 /// Interface (link) flags
 bitflags! {
     pub flags IfFlags: u32 {
@@ -180,253 +226,772 @@ pub enum OperState {
     Up = 6,
 }
 
+impl OperState {
+    fn from_u8(val: u8) -> Result<Self> {
+        match val {
+            0 => Ok(OperState::Unknown),
+            1 => Ok(OperState::NotPresent),
+            2 => Ok(OperState::Down),
+            3 => Ok(OperState::LowerLayerDown),
+            4 => Ok(OperState::Testing),
+            5 => Ok(OperState::Dormant),
+            6 => Ok(OperState::Up),
+            _ => Err(Error::Malformed("operating state")),
+        }
+    }
+}
+
+/// Changes applied to a link in one request, see `Links::set_link`
+///
+/// Only flags passed to `set_flags` (or `up`/`down`) are changed,
+/// others keep their current values.
+pub struct LinkChange {
+    flags: IfFlags,
+    change: IfFlags,
+    attrs: NlaBuilder,
+}
+
+impl Default for LinkChange {
+    fn default() -> Self {
+        LinkChange::new()
+    }
+}
+
+impl LinkChange {
+    pub fn new() -> Self {
+        LinkChange {
+            flags: IfFlags::empty(),
+            change: IfFlags::empty(),
+            attrs: NlaBuilder::new(),
+        }
+    }
+
+    /// Sets (`enable`) or clears `flags`
+    pub fn set_flags(mut self, flags: IfFlags, enable: bool) -> Self {
+        if enable {
+            self.flags.insert(flags);
+        } else {
+            self.flags.remove(flags);
+        }
+        self.change.insert(flags);
+        self
+    }
+
+    /// Brings the link up
+    pub fn up(self) -> Self {
+        self.set_flags(UP, true)
+    }
+
+    /// Brings the link down
+    pub fn down(self) -> Self {
+        self.set_flags(UP, false)
+    }
+
+    pub fn mtu(self, mtu: u32) -> Self {
+        self.nla(IFLA_MTU, mtu)
+    }
+
+    pub fn hw_addr(self, addr: MacAddr) -> Self {
+        self.nla(IFLA_ADDRESS, addr)
+    }
+
+    pub fn name(self, name: &str) -> Self {
+        self.nla(IFLA_IFNAME, name)
+    }
+
+    pub fn txqlen(self, txqlen: u32) -> Self {
+        self.nla(IFLA_TXQLEN, txqlen)
+    }
+
+    pub fn alias(self, alias: &str) -> Self {
+        self.nla(IFLA_IFALIAS, alias)
+    }
+
+    pub fn group(self, group: u32) -> Self {
+        self.nla(IFLA_GROUP, group)
+    }
+
+    /// Enslaves the link to link `index`, 0 releases it
+    pub fn master(self, index: u32) -> Self {
+        self.nla(IFLA_MASTER, index)
+    }
+
+    pub fn proto_down(self, down: bool) -> Self {
+        self.nla(IFLA_PROTO_DOWN, down as u8)
+    }
+
+    /// Appends attribute `kind` with value `value`
+    pub fn nla<T: Nla>(mut self, kind: u16, value: T) -> Self {
+        self.attrs = self.attrs.append(kind, value);
+        self
+    }
+}
+
+/// Virtual link to create, see `Links::create_link`
+pub struct LinkSpec {
+    name: String,
+    kind: LinkType,
+    change: LinkChange,
+    info_data: Option<NlaBuilder>,
+}
+
+impl LinkSpec {
+    /// Link `name` of type `kind`
+    pub fn new(name: &str, kind: LinkType) -> Self {
+        LinkSpec {
+            name: name.to_owned(),
+            kind: kind,
+            change: LinkChange::new(),
+            info_data: None,
+        }
+    }
+
+    /// Name of the link to create
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Attributes to send nested in IFLA_INFO_DATA
+    pub fn get_info_data(&self) -> Option<&NlaBuilder> {
+        self.info_data.as_ref()
+    }
+
+    /// Brings the link up once created
+    pub fn up(mut self) -> Self {
+        self.change = self.change.up();
+        self
+    }
+
+    pub fn mtu(mut self, mtu: u32) -> Self {
+        self.change = self.change.mtu(mtu);
+        self
+    }
+
+    pub fn hw_addr(mut self, addr: MacAddr) -> Self {
+        self.change = self.change.hw_addr(addr);
+        self
+    }
+
+    /// Enslaves the link to link `index`
+    pub fn master(mut self, index: u32) -> Self {
+        self.change = self.change.master(index);
+        self
+    }
+
+    /// Parent link, e.g. for vlan or macvlan
+    pub fn link(self, index: u32) -> Self {
+        self.nla(IFLA_LINK, index)
+    }
+
+    /// Kind specific attributes, sent nested in IFLA_INFO_DATA
+    pub fn info_data(mut self, data: NlaBuilder) -> Self {
+        self.info_data = Some(data);
+        self
+    }
+
+    /// Appends attribute `kind` with value `value`
+    pub fn nla<T: Nla>(mut self, kind: u16, value: T) -> Self {
+        self.change = self.change.nla(kind, value);
+        self
+    }
+}
+
 /// Link is a virtual of physical interface
 pub struct Link {
     packet: NetlinkPacket<'static>
 }
 
+/// Link borrowed from a receive buffer, see `Links::dump_links`
+#[derive(Clone,Copy)]
+pub struct LinkView<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> LinkView<'a> {
+    /// Parses RTM_NEWLINK or RTM_DELLINK message
+    pub fn from_bytes(data: &'a [u8]) -> Result<Self> {
+        let (kind, len) = match NetlinkPacket::new(data) {
+            Some(pkt) => (pkt.get_kind(), pkt.get_length() as usize),
+            None => return Err(Error::Truncated),
+        };
+        if kind != RTM_NEWLINK && kind != RTM_DELLINK {
+            return Err(Error::Malformed("not a link message"));
+        }
+        let data = &data[..::std::cmp::min(len, data.len())];
+        if data.len() < NetlinkPacket::minimum_packet_size() + IfInfoPacket::minimum_packet_size() {
+            return Err(Error::Truncated);
+        }
+        Ok(LinkView { data: data })
+    }
+
+    /// Copies the message into an owned `Link`
+    pub fn to_link(&self) -> Link {
+        Link { packet: NetlinkPacket::owned(self.data.to_owned()).unwrap() }
+    }
+
+    /// Address family, AF_UNSPEC for plain link messages
+    pub fn get_family(&self) -> u8 {
+        self.ifinfo().get_family()
+    }
+
+    /// Get link's unique index
+    pub fn get_index(&self) -> u32 {
+        self.ifinfo().get_index()
+    }
+
+    /// Get link's type
+    pub fn get_type(&self) -> IfType {
+        self.ifinfo().get_type_()
+    }
+
+    /// Get link's flags
+    pub fn get_flags(&self) -> IfFlags {
+        self.ifinfo().get_flags()
+    }
+
+    /// Get hardware address, `None` if missing or not an Ethernet address
+    pub fn get_hw_addr(&self) -> Option<MacAddr> {
+        self.get_nla(IFLA_ADDRESS).unwrap_or(None)
+    }
+
+    /// Get MTU
+    pub fn get_mtu(&self) -> Result<Option<u32>> {
+        self.get_nla(IFLA_MTU)
+    }
+
+    /// Queueing discipline
+    pub fn get_qdisc(&self) -> Result<Option<&'a str>> {
+        self.attrs().get_str(IFLA_QDISC)
+    }
+
+    /// Get operating state, `Unknown` if the kernel did not report it
+    pub fn get_state(&self) -> Result<OperState> {
+        match try!(self.get_nla(IFLA_OPERSTATE)) {
+            Some(state) => OperState::from_u8(state),
+            None => Ok(OperState::Unknown),
+        }
+    }
+
+    /// Get broadcast address, `None` if missing or not an Ethernet address
+    pub fn get_broadcast(&self) -> Option<MacAddr> {
+        self.get_nla(IFLA_BROADCAST).unwrap_or(None)
+    }
+
+    /// Get name
+    pub fn get_name(&self) -> Result<Option<&'a str>> {
+        self.attrs().get_str(IFLA_IFNAME)
+    }
+
+    /// Id of the namespace the link's peer lives in,
+    /// see `NetnsIds::get_netns_name`
+    pub fn get_link_netnsid(&self) -> Result<Option<i32>> {
+        self.get_nla(IFLA_LINK_NETNSID)
+    }
+
+    /// Index of the master link, e.g. the bridge of a bridge port
+    pub fn get_master(&self) -> Result<Option<u32>> {
+        self.get_nla(IFLA_MASTER)
+    }
+
+    /// Kind of a virtual link, e.g. "vlan"
+    pub fn get_kind(&self) -> Result<Option<&'a str>> {
+        match self.attrs().find_kind(IFLA_LINKINFO) {
+            Some(linkinfo) => linkinfo.nested().get_str(IFLA_INFO_KIND),
+            None => Ok(None),
+        }
+    }
+
+    /// Kind specific attributes nested in IFLA_INFO_DATA
+    pub fn get_info_data(&self) -> Option<NlaIterator<'a>> {
+        self.attrs().find_kind(IFLA_LINKINFO)
+            .and_then(|linkinfo| linkinfo.nested().find_kind(IFLA_INFO_DATA))
+            .map(|data| data.nested())
+    }
+
+    /// Kind of the master a port link is enslaved to, e.g. "bridge"
+    pub fn get_slave_kind(&self) -> Result<Option<&'a str>> {
+        match self.attrs().find_kind(IFLA_LINKINFO) {
+            Some(linkinfo) => linkinfo.nested().get_str(IFLA_INFO_SLAVE_KIND),
+            None => Ok(None),
+        }
+    }
+
+    /// Port attributes nested in IFLA_INFO_SLAVE_DATA
+    pub fn get_slave_data(&self) -> Option<NlaIterator<'a>> {
+        self.attrs().find_kind(IFLA_LINKINFO)
+            .and_then(|linkinfo| linkinfo.nested().find_kind(IFLA_INFO_SLAVE_DATA))
+            .map(|data| data.nested())
+    }
+
+    /// Decodes attribute `kind`, `None` if it is missing
+    pub fn get_nla<T: FromNla>(&self, kind: u16) -> Result<Option<T>> {
+        self.attrs().get(kind)
+    }
+
+    /// Iterates over link attributes
+    pub fn attrs(&self) -> NlaIterator<'a> {
+        let offset = NetlinkPacket::minimum_packet_size() + IfInfoPacket::minimum_packet_size();
+        NlaIterator::new(&self.data[offset..])
+    }
+
+    fn ifinfo(&self) -> IfInfoPacket<'a> {
+        IfInfoPacket::new(&self.data[NetlinkPacket::minimum_packet_size()..]).unwrap()
+    }
+}
+
+impl<'a> ::std::fmt::Debug for LinkView<'a> {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "{}: {:?}", self.get_index(), self.get_name().unwrap_or(None))
+    }
+}
+
+/// Lending iterator over a links dump, see `Links::dump_links`
+pub struct LinkDump<R: Read> {
+    reader: NetlinkReader<R>,
+}
+
+impl<R: Read> LinkDump<R> {
+    /// Returns next link, it borrows the receive buffer
+    /// until the next call
+    pub fn next_link(&mut self) -> Result<Option<LinkView>> {
+        match try!(self.reader.next_message()) {
+            Some(data) => {
+                if NetlinkPacket::new(data).unwrap().get_kind() != RTM_NEWLINK {
+                    return Ok(None);
+                }
+                LinkView::from_bytes(data).map(Some)
+            },
+            None => Ok(None),
+        }
+    }
+}
+
 pub struct LinksIterator<R: Read> {
     iter: NetlinkBufIterator<R>,
 }
 
 impl<R: Read> Iterator for LinksIterator<R> {
-    type Item = Link;
+    type Item = Result<Link>;
 
     fn next(&mut self) -> Option<Self::Item> {
         match self.iter.next() {
-            Some(pkt) => {
+            Some(Ok(pkt)) => {
                 let kind = pkt.get_kind();
                 if kind != RTM_NEWLINK {
                     return None;
                 }
-                return Some(Link { packet: pkt });
+                return Some(Link::from_packet(pkt));
             },
+            Some(Err(e)) => Some(Err(e)),
             None => None,
         }
     }
 }
 
+impl<R: Read> LinksIterator<R> {
+    /// Returns the last link of a single link request,
+    /// `None` if the kernel reports there is no such device
+    fn single(self) -> Result<Option<Link>> {
+        let mut found = None;
+        for link in self {
+            match link {
+                Ok(link) => found = Some(link),
+                Err(Error::Kernel(ref e)) if e.errno() == libc::ENODEV => return Ok(None),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(found)
+    }
+}
+
 impl ::std::fmt::Debug for Link {
     fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
-        write!(f, "{}: {:?}", self.get_index(), self.get_name())
+        write!(f, "{}: {:?}", self.get_index(), self.get_name().unwrap_or(None))
     }
 }
 
 /// Links operation trait
 pub trait Links where Self: Read + Write {
     /// iterate over links
-    fn iter_links(&mut self) -> io::Result<Box<LinksIterator<&mut Self>>>;
+    fn iter_links(&mut self) -> Result<Box<LinksIterator<&mut Self>>>;
+    /// iterate over links without copying them out of the receive buffer
+    fn dump_links(&mut self) -> Result<LinkDump<&mut Self>>;
+    /// returns a consistent snapshot of all links, see `DumpPolicy`
+    fn snapshot_links(&mut self, policy: DumpPolicy) -> Result<Vec<Link>>;
     /// returns link by its index
-    fn get_link_by_index(&mut self, index: u32) -> io::Result<Option<Link>>;
+    fn get_link_by_index(&mut self, index: u32) -> Result<Option<Link>>;
     /// returns link by its name
-    fn get_link_by_name(&mut self, name: &str) -> io::Result<Option<Link>>;
+    fn get_link_by_name(&mut self, name: &str) -> Result<Option<Link>>;
     /// delete link
-    fn delete_link(&mut self, link: Link) -> io::Result<()>;
+    fn delete_link(&mut self, link: Link) -> Result<()>;
     /// create dummy link
-    fn new_dummy_link(&mut self, name: &str) -> io::Result<()>;
+    fn new_dummy_link(&mut self, name: &str) -> Result<()>;
+    /// move link to network namespace `target`, renaming it to `new_name` if given
+    fn move_link_to_netns(&mut self, link: &Link, target: &NetNs, new_name: Option<&str>) -> Result<()>;
+    /// apply `change` to link
+    fn set_link(&mut self, link: &Link, change: LinkChange) -> Result<()>;
+    /// create virtual link described by `spec`, `Error::LinkVanished`
+    /// if it is gone before it could be fetched
+    fn create_link(&mut self, spec: LinkSpec) -> Result<Link>;
+    /// create veth pair described by `spec`, returns both ends
+    fn create_veth(&mut self, spec: VethSpec) -> Result<(Link, Link)>;
 }
 
 impl Links for NetlinkConnection {
-    fn iter_links(&mut self) -> io::Result<Box<LinksIterator<&mut Self>>> {
-        let req = NetlinkRequestBuilder::new(RTM_GETLINK, NLM_F_DUMP)
-            .append(
-                IfInfoPacketBuilder::new()
-                    .build()
-            ).build();
-        try!(self.write(req.packet()));
-        let reader = NetlinkReader::new(self);
+    fn iter_links(&mut self) -> Result<Box<LinksIterator<&mut Self>>> {
+        let req = dump_links_request();
+        let reader = try!(self.send(req));
         Ok(Box::new(LinksIterator { iter: reader.into_iter() }))
     }
 
-    fn get_link_by_index(&mut self, index: u32) -> io::Result<Option<Link>> {
-        let mut req = {
-            let mut buf = vec![0; MutableIfInfoPacket::minimum_packet_size()];
-            NetlinkRequestBuilder::new(RTM_GETLINK, NLM_F_ACK)
-            .append(
-                IfInfoPacketBuilder::new()
-                    .set_index(index)
-                    .build()
-            ).build()
-        };
-        try!(self.write(req.packet()));
-        let reader = NetlinkReader::new(self);
+    fn dump_links(&mut self) -> Result<LinkDump<&mut Self>> {
+        let req = dump_links_request();
+        let reader = try!(self.send(req));
+        Ok(LinkDump { reader: reader })
+    }
+
+    fn snapshot_links(&mut self, policy: DumpPolicy) -> Result<Vec<Link>> {
+        let pkts = try!(self.dump(&dump_links_request(), policy));
+        pkts.into_iter()
+            .filter(|pkt| pkt.get_kind() == RTM_NEWLINK)
+            .map(Link::from_packet)
+            .collect()
+    }
+
+    fn get_link_by_index(&mut self, index: u32) -> Result<Option<Link>> {
+        let req = link_by_index_request(index);
+        let reader = try!(self.send(req));
         let li = LinksIterator { iter: reader.into_iter() };
-        Ok(li.last())
-    }
-
-    fn get_link_by_name(&mut self, name: &str) -> io::Result<Option<Link>> {
-        let req = {
-            let name_len = name.as_bytes().len();
-            NetlinkRequestBuilder::new(RTM_GETLINK, NLM_F_ACK).append({
-                let mut buf = vec![0; RtAttrPacket::minimum_packet_size() + name_len + 1];
-                IfInfoPacketBuilder::new().append({
-                    {
-                        let mut ifname_rta = MutableRtAttrPacket::new(&mut buf).unwrap();
-                        ifname_rta.set_rta_type(IFLA_IFNAME);
-                        ifname_rta.set_rta_len((RtAttrPacket::minimum_packet_size() + name_len + 1) as u16);
-                        let mut payload = ifname_rta.payload_mut();
-                        payload[0..name_len].copy_from_slice(name.as_bytes());
-                    }
-                    RtAttrPacket::new(&buf[..]).unwrap()
-                }).build()
-            }).build()
-        };
-        try!(self.write(req.packet()));
-        let reader = NetlinkReader::new(self);
+        li.single()
+    }
+
+    fn get_link_by_name(&mut self, name: &str) -> Result<Option<Link>> {
+        let req = link_by_name_request(name);
+        let reader = try!(self.send(req));
         let li = LinksIterator { iter: reader.into_iter() };
-        Ok(li.last())
-    }
-
-    fn new_dummy_link(&mut self, name: &str) -> io::Result<()> {
-        let mut ifi = {
-            let mut buf = vec![0; 32];
-            let name_len = name.as_bytes().len();
-            let mut buf_name = vec![0; RtAttrPacket::minimum_packet_size() + name_len + 1];
-            IfInfoPacketBuilder::new().
-                append({
-                    {
-                        let mut ifname_rta = MutableRtAttrPacket::new(&mut buf_name).unwrap();
-                        ifname_rta.set_rta_type(IFLA_IFNAME);
-                        ifname_rta.set_rta_len(4 + name_len as u16 + 1);
-                        let mut payload = ifname_rta.payload_mut();
-                        payload[0..name_len].copy_from_slice(name.as_bytes());
-                    }
-                    RtAttrPacket::new(&buf_name).unwrap()
-                }).
-                append({
-                    {
-                        let mut link_info_rta = MutableRtAttrPacket::new(&mut buf).unwrap();
-                        link_info_rta.set_rta_type(IFLA_LINKINFO);
-                        link_info_rta.set_rta_len(6 + 4 + 4);
-                        let mut info_kind_rta = MutableRtAttrPacket::new(link_info_rta.payload_mut()).unwrap();
-                        info_kind_rta.set_rta_type(IFLA_INFO_KIND);
-                        info_kind_rta.set_rta_len(6 + 4);
-                        let mut payload = info_kind_rta.payload_mut();
-                        payload[0..6].copy_from_slice(b"dummy\0");
-                    }
-                    RtAttrPacket::new(&buf).unwrap()
-            }).build()
-        };
-        let req = NetlinkRequestBuilder::new(RTM_NEWLINK, NLM_F_CREATE | NLM_F_EXCL | NLM_F_ACK)
-            .append(ifi).build();
-        try!(self.write(req.packet()));
-        let reader = NetlinkReader::new(self);
+        li.single()
+    }
+
+    fn new_dummy_link(&mut self, name: &str) -> Result<()> {
+        let req = new_dummy_link_request(name);
+        let reader = try!(self.send(req));
         reader.read_to_end()
     }
 
-    fn delete_link(&mut self, link: Link) -> io::Result<()> {
-        let index = link.get_index();
-        let mut req = {
-            let mut buf = vec![0; MutableIfInfoPacket::minimum_packet_size()];
-            NetlinkRequestBuilder::new(RTM_DELLINK, NLM_F_ACK)
-            .append({
-                let mut ifinfo = MutableIfInfoPacket::new(&mut buf).unwrap();
-                ifinfo.set_family(0 /* AF_UNSPEC */);
-                ifinfo.set_index(index);
-                ifinfo
-            }).build()
-        };
-        try!(self.write(req.packet()));
-        let reader = NetlinkReader::new(self);
+    fn delete_link(&mut self, link: Link) -> Result<()> {
+        let req = delete_link_request(link.get_index());
+        let reader = try!(self.send(req));
+        reader.read_to_end()
+    }
+
+    fn move_link_to_netns(&mut self, link: &Link, target: &NetNs, new_name: Option<&str>) -> Result<()> {
+        let (req, _file) = try!(move_link_request(link.get_index(), target, new_name));
+        let reader = try!(self.send(req));
+        reader.read_to_end()
+    }
+
+    fn set_link(&mut self, link: &Link, change: LinkChange) -> Result<()> {
+        let req = set_link_request(link.get_index(), change);
+        let reader = try!(self.send(req));
         reader.read_to_end()
     }
+
+    fn create_link(&mut self, spec: LinkSpec) -> Result<Link> {
+        let name = spec.get_name().to_owned();
+        let req = create_link_request(spec);
+        let reader = try!(self.send(req));
+        try!(reader.read_to_end());
+        match try!(self.get_link_by_name(&name)) {
+            Some(link) => Ok(link),
+            None => Err(Error::LinkVanished(name)),
+        }
+    }
+
+    fn create_veth(&mut self, spec: VethSpec) -> Result<(Link, Link)> {
+        let peer_name = spec.get_peer_name().to_owned();
+        let peer_netns = spec.get_peer_netns().cloned();
+        let (spec, _file) = try!(spec.into_link_spec());
+        let link = try!(self.create_link(spec));
+        // the peer is only visible from its own namespace
+        let peer = match peer_netns {
+            Some(netns) => try!(try!(NetlinkConnection::new_in_netns(&netns)).get_link_by_name(&peer_name)),
+            None => try!(self.get_link_by_name(&peer_name)),
+        };
+        match peer {
+            Some(peer) => Ok((link, peer)),
+            None => Err(Error::LinkVanished(peer_name)),
+        }
+    }
+}
+
+/// Async counterpart of `Links`
+#[cfg(feature = "async")]
+pub trait AsyncLinks {
+    /// iterate over links
+    fn iter_links<'a>(&'a mut self) -> BoxFuture<'a, Result<BoxStream<'a, Result<Link>>>>;
+    /// returns link by its index
+    fn get_link_by_index<'a>(&'a mut self, index: u32) -> BoxFuture<'a, Result<Option<Link>>>;
+    /// returns link by its name
+    fn get_link_by_name<'a>(&'a mut self, name: &str) -> BoxFuture<'a, Result<Option<Link>>>;
+    /// delete link
+    fn delete_link<'a>(&'a mut self, link: Link) -> BoxFuture<'a, Result<()>>;
+    /// create dummy link
+    fn new_dummy_link<'a>(&'a mut self, name: &str) -> BoxFuture<'a, Result<()>>;
+    /// move link to network namespace `target`, renaming it to `new_name` if given
+    fn move_link_to_netns<'a>(&'a mut self, link: &Link, target: &NetNs, new_name: Option<&str>) -> BoxFuture<'a, Result<()>>;
+    /// apply `change` to link
+    fn set_link<'a>(&'a mut self, link: &Link, change: LinkChange) -> BoxFuture<'a, Result<()>>;
+}
+
+#[cfg(feature = "async")]
+impl AsyncLinks for AsyncNetlinkConnection {
+    fn iter_links<'a>(&'a mut self) -> BoxFuture<'a, Result<BoxStream<'a, Result<Link>>>> {
+        self.send(dump_links_request()).map_ok(|replies| {
+            replies.try_take_while(|pkt| future::ready(Ok(pkt.get_kind() == RTM_NEWLINK)))
+                .and_then(|pkt| future::ready(Link::from_packet(pkt)))
+                .boxed()
+        }).boxed()
+    }
+
+    fn get_link_by_index<'a>(&'a mut self, index: u32) -> BoxFuture<'a, Result<Option<Link>>> {
+        self.send(link_by_index_request(index)).and_then(single_link).boxed()
+    }
+
+    fn get_link_by_name<'a>(&'a mut self, name: &str) -> BoxFuture<'a, Result<Option<Link>>> {
+        self.send(link_by_name_request(name)).and_then(single_link).boxed()
+    }
+
+    fn delete_link<'a>(&'a mut self, link: Link) -> BoxFuture<'a, Result<()>> {
+        self.send_ack(delete_link_request(link.get_index()))
+    }
+
+    fn new_dummy_link<'a>(&'a mut self, name: &str) -> BoxFuture<'a, Result<()>> {
+        self.send_ack(new_dummy_link_request(name))
+    }
+
+    fn move_link_to_netns<'a>(&'a mut self, link: &Link, target: &NetNs, new_name: Option<&str>) -> BoxFuture<'a, Result<()>> {
+        let (req, file) = match move_link_request(link.get_index(), target, new_name) {
+            Ok(req) => req,
+            Err(e) => return future::ready(Err(e)).boxed(),
+        };
+        // the namespace file must stay open until the request is sent
+        self.send_ack(req).map(move |res| { drop(file); res }).boxed()
+    }
+
+    fn set_link<'a>(&'a mut self, link: &Link, change: LinkChange) -> BoxFuture<'a, Result<()>> {
+        self.send_ack(set_link_request(link.get_index(), change))
+    }
+}
+
+/// Async counterpart of `LinksIterator::single`
+#[cfg(feature = "async")]
+fn single_link<'a>(replies: NetlinkStream<'a>) -> BoxFuture<'a, Result<Option<Link>>> {
+    replies.try_take_while(|pkt| future::ready(Ok(pkt.get_kind() == RTM_NEWLINK)))
+        .try_fold(None, |_, pkt| future::ready(Link::from_packet(pkt).map(Some)))
+        .or_else(|e| future::ready(match e {
+            Error::Kernel(ref e) if e.errno() == libc::ENODEV => Ok(None),
+            e => Err(e),
+        }))
+        .boxed()
+}
+
+fn dump_links_request() -> NetlinkPacket<'static> {
+    NetlinkRequestBuilder::new(RTM_GETLINK, NLM_F_DUMP)
+        .append(
+            IfInfoPacketBuilder::new()
+                .build()
+        ).build()
+}
+
+fn link_by_index_request(index: u32) -> NetlinkPacket<'static> {
+    NetlinkRequestBuilder::new(RTM_GETLINK, NLM_F_ACK)
+        .append(
+            IfInfoPacketBuilder::new()
+                .set_index(index)
+                .build()
+        ).build()
+}
+
+fn link_by_name_request(name: &str) -> NetlinkPacket<'static> {
+    NetlinkRequestBuilder::new(RTM_GETLINK, NLM_F_ACK)
+        .append(
+            IfInfoPacketBuilder::new()
+                .nla(IFLA_IFNAME, name)
+                .build()
+        ).build()
+}
+
+fn new_dummy_link_request(name: &str) -> NetlinkPacket<'static> {
+    create_link_request(LinkSpec::new(name, LinkType::Dummy))
+}
+
+fn create_link_request(spec: LinkSpec) -> NetlinkPacket<'static> {
+    let mut linkinfo = NlaBuilder::new().append(IFLA_INFO_KIND, spec.kind.kind());
+    if let Some(data) = spec.info_data {
+        linkinfo = linkinfo.nested(IFLA_INFO_DATA, data);
+    }
+    NetlinkRequestBuilder::new(RTM_NEWLINK, NLM_F_CREATE | NLM_F_EXCL | NLM_F_ACK)
+        .append(
+            IfInfoPacketBuilder::new()
+                .set_flags(spec.change.flags)
+                .set_change(spec.change.change)
+                .nla(IFLA_IFNAME, &spec.name[..])
+                .attrs(spec.change.attrs)
+                .nla(IFLA_LINKINFO, linkinfo)
+                .build()
+        ).build()
+}
+
+fn set_link_request(index: u32, change: LinkChange) -> NetlinkPacket<'static> {
+    NetlinkRequestBuilder::new(RTM_SETLINK, NLM_F_ACK)
+        .append(
+            IfInfoPacketBuilder::new()
+                .set_index(index)
+                .set_flags(change.flags)
+                .set_change(change.change)
+                .attrs(change.attrs)
+                .build()
+        ).build()
+}
+
+/// Builds RTM_SETLINK moving link `index` to `target`, the returned file
+/// must stay open until the request is sent
+fn move_link_request(index: u32, target: &NetNs, new_name: Option<&str>) -> Result<(NetlinkPacket<'static>, Option<File>)> {
+    let mut ifinfo = IfInfoPacketBuilder::new().set_index(index);
+    let mut file = None;
+    ifinfo = match *target {
+        NetNs::Pid(pid) => ifinfo.nla(IFLA_NET_NS_PID, pid),
+        NetNs::Fd(fd) => ifinfo.nla(IFLA_NET_NS_FD, fd as u32),
+        NetNs::Path(_) => {
+            file = try!(target.open());
+            match file {
+                Some(ref file) => ifinfo.nla(IFLA_NET_NS_FD, file.as_raw_fd() as u32),
+                None => ifinfo,
+            }
+        },
+    };
+    if let Some(name) = new_name {
+        ifinfo = ifinfo.nla(IFLA_IFNAME, name);
+    }
+    let req = NetlinkRequestBuilder::new(RTM_SETLINK, NLM_F_ACK)
+        .append(ifinfo.build())
+        .build();
+    Ok((req, file))
+}
+
+fn delete_link_request(index: u32) -> NetlinkPacket<'static> {
+    let mut buf = vec![0; MutableIfInfoPacket::minimum_packet_size()];
+    NetlinkRequestBuilder::new(RTM_DELLINK, NLM_F_ACK)
+    .append({
+        let mut ifinfo = MutableIfInfoPacket::new(&mut buf).unwrap();
+        ifinfo.set_family(0 /* AF_UNSPEC */);
+        ifinfo.set_index(index);
+        ifinfo
+    }).build()
 }
 
 impl Link {
+    /// Wraps RTM_NEWLINK or RTM_DELLINK message
+    pub fn from_packet(packet: NetlinkPacket<'static>) -> Result<Self> {
+        try!(LinkView::from_bytes(packet.packet()));
+        Ok(Link { packet: packet })
+    }
+
+    /// Borrows the link as a view, its accessors return borrowed strings
+    pub fn view(&self) -> LinkView {
+        LinkView { data: message_bytes(&self.packet) }
+    }
+
+    /// Address family, AF_UNSPEC for plain link messages
+    pub fn get_family(&self) -> u8 {
+        self.view().get_family()
+    }
+
     /// Get link's unique index
     pub fn get_index(&self) -> u32 {
-        self.with_ifinfo(|ifi| ifi.get_index())
+        self.view().get_index()
     }
 
     /// Get link's type
     pub fn get_type(&self) -> IfType {
-        self.with_ifinfo(|ifi| ifi.get_type_())
+        self.view().get_type()
     }
 
     /// Get link's flags
     pub fn get_flags(&self) -> IfFlags {
-        self.with_ifinfo(|ifi| ifi.get_flags())
+        self.view().get_flags()
     }
 
-    /// Get hardware address
+    /// Get hardware address, `None` if missing or not an Ethernet address
     pub fn get_hw_addr(&self) -> Option<MacAddr> {
-        self.with_rta(IFLA_ADDRESS, |rta| {
-            let payload = rta.payload();
-            MacAddr::new(payload[0], payload[1], payload[2], payload[3], payload[4], payload[5])
-        })
+        self.view().get_hw_addr()
     }
 
     /// Get MTU
-    pub fn get_mtu(&self) -> Option<u32> {
-        self.with_rta(IFLA_MTU, |rta| {
-            let mtu = RtAttrMtuPacket::new(rta.packet()).unwrap();
-            mtu.get_mtu()
-        })
+    pub fn get_mtu(&self) -> Result<Option<u32>> {
+        self.view().get_mtu()
     }
 
     /// Queueing discipline
-    pub fn get_qdisc(&self) -> Option<String> {
-        use std::ffi::CStr;
-        self.with_rta(IFLA_QDISC, |rta| {
-            let cstr = CStr::from_bytes_with_nul(rta.payload()).unwrap();
-            cstr.to_owned().into_string().unwrap()
-        })
+    pub fn get_qdisc(&self) -> Result<Option<String>> {
+        self.view().get_qdisc().map(|qdisc| qdisc.map(|qdisc| qdisc.to_owned()))
     }
 
-    /// Get operating state
-    pub fn get_state(&self) -> OperState {
-        use std::mem;
-        self.with_rta_iter(|mut rti| {
-            let rta = rti.find(|rta| rta.get_rta_type() == IFLA_OPERSTATE).unwrap();
-            unsafe { mem::transmute(rta.payload()[0]) }
-        })
+    /// Get operating state, `Unknown` if the kernel did not report it
+    pub fn get_state(&self) -> Result<OperState> {
+        self.view().get_state()
     }
 
-    /// Get broadcast address
+    /// Get broadcast address, `None` if missing or not an Ethernet address
     pub fn get_broadcast(&self) -> Option<MacAddr> {
-        self.with_rta(IFLA_BROADCAST, |rta| {
-            let payload = rta.payload();
-            MacAddr::new(payload[0], payload[1], payload[2], payload[3], payload[4], payload[5])
-        })
+        self.view().get_broadcast()
     }
 
     /// Get name
-    pub fn get_name(&self) -> Option<String> {
-        use std::ffi::CStr;
-        self.with_rta(IFLA_IFNAME, |rta| {
-            let payload = rta.payload();
-            let cstr = CStr::from_bytes_with_nul(rta.payload()).unwrap();
-            cstr.to_owned().into_string().unwrap()
-        })
+    pub fn get_name(&self) -> Result<Option<String>> {
+        self.view().get_name().map(|name| name.map(|name| name.to_owned()))
     }
 
-    // helper methods
-    fn with_packet<T,F>(&self, cb: F) -> T
-        where F: Fn(&NetlinkPacket) -> T {
-        cb(&self.packet)
+    /// Id of the namespace the link's peer lives in,
+    /// see `NetnsIds::get_netns_name`
+    pub fn get_link_netnsid(&self) -> Result<Option<i32>> {
+        self.view().get_link_netnsid()
     }
 
-    fn with_ifinfo<T,F>(&self, cb: F) -> T
-        where F: Fn(IfInfoPacket) -> T {
-        self.with_packet(|pkt| 
-            cb(IfInfoPacket::new(pkt.payload()).unwrap())
-        )
+    /// Index of the master link, e.g. the bridge of a bridge port
+    pub fn get_master(&self) -> Result<Option<u32>> {
+        self.view().get_master()
     }
 
-    fn with_rta_iter<T,F>(&self, cb: F) -> T
-        where F: Fn(RtAttrIterator) -> T {
-            self.with_ifinfo(|ifi| {
-                cb(RtAttrIterator::new(ifi.payload()))
-            })
+    /// Kind of a virtual link, e.g. "vlan"
+    pub fn get_kind(&self) -> Result<Option<String>> {
+        self.view().get_kind().map(|kind| kind.map(|kind| kind.to_owned()))
     }
 
-    fn with_rta<T,F>(&self, rta_type: u16, cb: F) -> Option<T>
-        where F: Fn(RtAttrPacket) -> T {
-        self.with_rta_iter(|mut rti| {
-            rti.find(|rta| rta.get_rta_type() == rta_type).map(|rta| cb(rta))
-        })
+    /// Kind specific attributes nested in IFLA_INFO_DATA
+    pub fn get_info_data(&self) -> Option<NlaIterator> {
+        self.view().get_info_data()
     }
 
+    /// Kind of the master a port link is enslaved to, e.g. "bridge"
+    pub fn get_slave_kind(&self) -> Result<Option<String>> {
+        self.view().get_slave_kind().map(|kind| kind.map(|kind| kind.to_owned()))
+    }
+
+    /// Port attributes nested in IFLA_INFO_SLAVE_DATA
+    pub fn get_slave_data(&self) -> Option<NlaIterator> {
+        self.view().get_slave_data()
+    }
+
+    /// Bridge port settings, `None` if the link is not a bridge port
+    pub fn get_bridge_port(&self) -> Result<Option<BridgePortInfo>> {
+        BridgePortInfo::from_link(self)
+    }
+
+    /// Decodes attribute `kind`, `None` if it is missing
+    pub fn get_nla<T: FromNla>(&self, kind: u16) -> Result<Option<T>> {
+        self.view().get_nla(kind)
+    }
 
     // static methods
     fn get_links_iter<R: Read>(r: NetlinkBufIterator<R>) -> LinksIterator<R> {
@@ -519,6 +1084,20 @@ impl IfInfoPacketBuilder {
         self
     }
 
+    pub fn set_change(mut self, change: IfFlags) -> Self {
+        {
+            let mut pkt = MutableIfInfoPacket::new(&mut self.data[..]).unwrap();
+            pkt.set_change(change.bits());
+        }
+        self
+    }
+
+    /// Appends encoded attributes `attrs`
+    pub fn attrs(mut self, attrs: NlaBuilder) -> Self {
+        self.data.extend_from_slice(&attrs.build());
+        self
+    }
+
     pub fn append(mut self, rta: RtAttrPacket) -> Self {
         let len = rta.get_rta_len() as usize;
         let aligned_len = ::util::align(len);
@@ -530,6 +1109,12 @@ impl IfInfoPacketBuilder {
         self
     }
 
+    /// Appends attribute `kind` with value `value`
+    pub fn nla<T: Nla>(mut self, kind: u16, value: T) -> Self {
+        emit_nla(&mut self.data, kind, &value);
+        self
+    }
+
     pub fn build(self) -> IfInfoPacket<'static> {
         IfInfoPacket::owned(self.data).unwrap()
     }
@@ -541,18 +1126,33 @@ mod tests {
     fn dump_links() {
         use ::packet::netlink::NetlinkConnection;
         use ::packet::route::link::{Link,Links};
-        let mut conn = NetlinkConnection::new();
+        let mut conn = NetlinkConnection::new().unwrap();
         for link in conn.iter_links().unwrap() {
-            Link::dump_link(link.packet.get_packet());
+            Link::dump_link(link.unwrap().packet);
         }
     }
 
+    #[cfg(feature = "async")]
+    #[test]
+    fn async_dump_links() {
+        use ::packet::async_netlink::AsyncNetlinkConnection;
+        use ::packet::route::link::{Link,AsyncLinks};
+        use futures::{TryFutureExt,TryStreamExt};
+        use tokio::runtime::Builder;
+
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        let _guard = rt.enter();
+        let mut conn = AsyncNetlinkConnection::new().unwrap();
+        let links: Vec<Link> = rt.block_on(conn.iter_links().and_then(|links| links.try_collect())).unwrap();
+        assert!(links.iter().any(|link| link.get_name().unwrap() == Some("lo".to_owned())));
+    }
+
     #[test]
     fn find_lo() {
         use ::packet::netlink::NetlinkConnection;
         use ::packet::route::link::{Link,Links};
 
-        let mut conn = NetlinkConnection::new();
+        let mut conn = NetlinkConnection::new().unwrap();
         let lo0 = conn.get_link_by_name("lo").unwrap();
         assert!(lo0.is_some());
         let lo0 = lo0.unwrap();
@@ -560,7 +1160,8 @@ mod tests {
         let lo1 = conn.get_link_by_index(idx).unwrap();
         assert!(lo1.is_some());
         let lo1 = lo1.unwrap();
-        assert!(lo1.get_name() == lo0.get_name());
+        assert!(lo1.get_name().unwrap() == lo0.get_name().unwrap());
+        assert!(conn.get_link_by_name("nonexistent0").unwrap().is_none());
     }
 
     #[test]
@@ -569,13 +1170,120 @@ mod tests {
         use ::packet::netlink::NetlinkConnection;
         use ::packet::route::link::{Link,Links};
 
-        let mut conn = NetlinkConnection::new();
+        let mut conn = NetlinkConnection::new().unwrap();
         conn.new_dummy_link("test1488").unwrap();
         let link = conn.get_link_by_name("test1488").unwrap().unwrap();
-        assert!(link.get_name() == Some("test1488".to_owned()));
-        conn.iter_links().unwrap().find(|link| link.get_name() == Some("test1488".to_owned())).is_some();
-        conn.delete_link(link);
-        conn.iter_links().unwrap().find(|link| link.get_name() == Some("test1488".to_owned())).is_none();
+        assert!(link.get_name().unwrap() == Some("test1488".to_owned()));
+        conn.iter_links().unwrap().find(|link| link.as_ref().unwrap().get_name().unwrap() == Some("test1488".to_owned())).is_some();
+        conn.delete_link(link).unwrap();
+        conn.iter_links().unwrap().find(|link| link.as_ref().unwrap().get_name().unwrap() == Some("test1488".to_owned())).is_none();
+    }
+
+    #[test]
+    fn if_type_values() {
+        use ::packet::route::link::IfType;
+
+        assert_eq!(IfType::new(772), IfType::Loopback);
+        assert_eq!(IfType::new(65534), IfType::Other(65534));
+        for &val in &[0, 1, 772, 65534] {
+            assert_eq!(IfType::new(val).value(), val);
+        }
+    }
+
+    #[test]
+    fn create_link_request_attrs() {
+        use ::packet::netlink::{NLM_F_REQUEST,NLM_F_CREATE,NLM_F_EXCL,NLM_F_ACK};
+        use ::packet::route::IfInfoPacket;
+        use ::packet::route::link::{LinkSpec,LinkType,create_link_request};
+        use ::packet::route::link::{RTM_NEWLINK,IFLA_IFNAME,IFLA_LINKINFO,IFLA_INFO_KIND,IFLA_INFO_DATA};
+        use ::packet::route::nla::{NlaBuilder,NlaIterator};
+        use pnet::packet::Packet;
+
+        let spec = LinkSpec::new("br0", LinkType::Bridge)
+            .info_data(NlaBuilder::new().append(7u16, 42u32));
+        let req = create_link_request(spec);
+        assert_eq!(req.get_kind(), RTM_NEWLINK);
+        assert_eq!(req.get_flags(), NLM_F_REQUEST | NLM_F_CREATE | NLM_F_EXCL | NLM_F_ACK);
+        let ifi = IfInfoPacket::new(req.payload()).unwrap();
+        let attrs = NlaIterator::new(ifi.payload());
+        assert_eq!(attrs.clone().get_str(IFLA_IFNAME).unwrap(), Some("br0"));
+        let linkinfo = attrs.find_kind(IFLA_LINKINFO).unwrap();
+        assert_eq!(linkinfo.nested().get_str(IFLA_INFO_KIND).unwrap(), Some("bridge"));
+        let data = linkinfo.nested().find_kind(IFLA_INFO_DATA).unwrap();
+        assert!(data.is_nested());
+        assert_eq!(data.nested().get::<u32>(7).unwrap(), Some(42));
+    }
+
+    #[test]
+    fn set_link_request_attrs() {
+        use ::packet::netlink::{NLM_F_REQUEST,NLM_F_ACK};
+        use ::packet::route::IfInfoPacket;
+        use ::packet::route::link::{LinkChange,IfFlags,UP,set_link_request};
+        use ::packet::route::link::{RTM_SETLINK,IFLA_IFNAME,IFLA_MTU,IFLA_MASTER};
+        use ::packet::route::nla::NlaIterator;
+        use pnet::packet::Packet;
+
+        let req = set_link_request(5, LinkChange::new().up().mtu(9000).name("eth1").master(3));
+        assert_eq!(req.get_kind(), RTM_SETLINK);
+        assert_eq!(req.get_flags(), NLM_F_REQUEST | NLM_F_ACK);
+        let ifi = IfInfoPacket::new(req.payload()).unwrap();
+        assert_eq!(ifi.get_index(), 5);
+        assert_eq!((ifi.get_flags(), ifi.get_change()), (UP, UP.bits()));
+        let attrs = NlaIterator::new(ifi.payload());
+        assert_eq!(attrs.clone().get::<u32>(IFLA_MTU).unwrap(), Some(9000));
+        assert_eq!(attrs.clone().get_str(IFLA_IFNAME).unwrap(), Some("eth1"));
+        assert_eq!(attrs.get::<u32>(IFLA_MASTER).unwrap(), Some(3));
+
+        // down clears UP but still marks it changed, master(0) releases
+        let req = set_link_request(5, LinkChange::new().down().master(0));
+        let ifi = IfInfoPacket::new(req.payload()).unwrap();
+        assert_eq!((ifi.get_flags(), ifi.get_change()), (IfFlags::empty(), UP.bits()));
+        let attrs = NlaIterator::new(ifi.payload());
+        assert_eq!(attrs.clone().get::<u32>(IFLA_MASTER).unwrap(), Some(0));
+        assert_eq!(attrs.clone().get::<u32>(IFLA_MTU).unwrap(), None);
+
+        // nothing changed, nothing sent
+        let req = set_link_request(5, LinkChange::new());
+        let ifi = IfInfoPacket::new(req.payload()).unwrap();
+        assert_eq!(ifi.get_change(), 0);
+        assert_eq!(NlaIterator::new(ifi.payload()).count(), 0);
+    }
+
+    #[test]
+    fn move_link_request_attrs() {
+        use ::packet::netlink::{NLM_F_REQUEST,NLM_F_ACK};
+        use ::packet::route::IfInfoPacket;
+        use ::packet::route::link::{move_link_request,RTM_SETLINK};
+        use ::packet::route::link::{IFLA_IFNAME,IFLA_NET_NS_PID,IFLA_NET_NS_FD};
+        use ::packet::route::nla::NlaIterator;
+        use ::netns::NetNs;
+        use pnet::packet::Packet;
+        use std::os::unix::io::AsRawFd;
+
+        let (req, file) = move_link_request(5, &NetNs::Pid(42), Some("eth1")).unwrap();
+        assert!(file.is_none());
+        assert_eq!(req.get_kind(), RTM_SETLINK);
+        assert_eq!(req.get_flags(), NLM_F_REQUEST | NLM_F_ACK);
+        let ifi = IfInfoPacket::new(req.payload()).unwrap();
+        assert_eq!(ifi.get_index(), 5);
+        let attrs = NlaIterator::new(ifi.payload());
+        assert_eq!(attrs.clone().get::<u32>(IFLA_NET_NS_PID).unwrap(), Some(42));
+        assert_eq!(attrs.clone().get::<u32>(IFLA_NET_NS_FD).unwrap(), None);
+        assert_eq!(attrs.get_str(IFLA_IFNAME).unwrap(), Some("eth1"));
+
+        // the namespace file is passed by descriptor and kept open
+        let (req, file) = move_link_request(5, &NetNs::Path("/proc/self/ns/net".into()), None).unwrap();
+        let fd = file.unwrap().as_raw_fd() as u32;
+        let ifi = IfInfoPacket::new(req.payload()).unwrap();
+        let attrs = NlaIterator::new(ifi.payload());
+        assert_eq!(attrs.clone().get::<u32>(IFLA_NET_NS_FD).unwrap(), Some(fd));
+        assert_eq!(attrs.clone().get::<u32>(IFLA_NET_NS_PID).unwrap(), None);
+        assert_eq!(attrs.get_str(IFLA_IFNAME).unwrap(), None);
+
+        let (req, file) = move_link_request(5, &NetNs::Fd(7), None).unwrap();
+        assert!(file.is_none());
+        let ifi = IfInfoPacket::new(req.payload()).unwrap();
+        assert_eq!(NlaIterator::new(ifi.payload()).get::<u32>(IFLA_NET_NS_FD).unwrap(), Some(7));
     }
 
 }
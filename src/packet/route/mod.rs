@@ -15,12 +15,60 @@ use libc;
 use std::io::Read;
 
 pub mod addr;
+pub mod bridge;
+pub mod cache;
 pub mod link;
+pub mod monitor;
 pub mod neighbour;
+pub mod nla;
+pub mod nsid;
 pub mod route;
 pub mod rule;
+pub mod veth;
+pub mod vlan;
 
-include!(concat!(env!("OUT_DIR"), "/route/route.rs"));
+include!("route.rs.in");
+
+/// RTNETLINK multicast groups, see `NetlinkSocket::join_group`
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+#[repr(u32)]
+pub enum RtnlGroup {
+    Link = 1,
+    Notify = 2,
+    Neigh = 3,
+    Tc = 4,
+    Ipv4Ifaddr = 5,
+    Ipv4Mroute = 6,
+    Ipv4Route = 7,
+    Ipv4Rule = 8,
+    Ipv6Ifaddr = 9,
+    Ipv6Mroute = 10,
+    Ipv6Route = 11,
+    Ipv6Ifinfo = 12,
+    Ipv6Prefix = 18,
+    Ipv6Rule = 19,
+    NdUseropt = 20,
+    Dcb = 23,
+    Ipv4Netconf = 24,
+    Ipv6Netconf = 25,
+    Mdb = 26,
+    MplsRoute = 27,
+    Nsid = 28,
+    MplsNetconf = 29,
+    Ipv4MrouteR = 30,
+    Ipv6MrouteR = 31,
+    Nexthop = 32,
+    Brvlan = 33,
+    MctpIfaddr = 34,
+    Tunnel = 35,
+    Stats = 36,
+}
+
+impl From<RtnlGroup> for u32 {
+    fn from(group: RtnlGroup) -> u32 {
+        group as u32
+    }
+}
 
 const RTA_ALIGNTO: usize = 4;
 
@@ -34,7 +82,8 @@ pub struct RtAttrIterator<'a> {
 }
 
 impl<'a> RtAttrIterator<'a> {
-    fn new(buf: &'a [u8]) -> Self {
+    /// Iterates over attributes in `buf`, stops at the first malformed one
+    pub fn new(buf: &'a [u8]) -> Self {
         RtAttrIterator {
             buf: buf,
         }
@@ -47,14 +96,29 @@ impl<'a> Iterator for RtAttrIterator<'a> {
     fn next(&mut self) -> Option<Self::Item> {
         if let Some(rta) = RtAttrPacket::new(&self.buf[..]) {
             let len = rta.get_rta_len() as usize; 
-            if len < 4 {
+            if len < 4 || len > self.buf.len() {
                 return None;
             }
-            self.buf = &self.buf[align(len as usize)..];
+            let next = ::std::cmp::min(align(len as usize), self.buf.len());
+            self.buf = &self.buf[next..];
             return Some(rta);
         }
         None
     }
 }
 
+/// Wraps `msg` in an unsolicited message of type `kind`, as sent by the kernel
+#[cfg(test)]
+fn message<P: PacketSize + Packet>(kind: u16, msg: P) -> NetlinkPacket<'static> {
+    use packet::netlink::{NetlinkRequestBuilder,NetlinkMsgFlags};
+
+    NetlinkRequestBuilder::new(kind, NetlinkMsgFlags::empty()).append(msg).build()
+}
 
+/// Link message carrying only the interface index
+#[cfg(test)]
+fn link_message(kind: u16, index: u32) -> NetlinkPacket<'static> {
+    let mut ifinfo = MutableIfInfoPacket::owned(vec![0; 16]).unwrap();
+    ifinfo.set_index(index);
+    message(kind, ifinfo)
+}
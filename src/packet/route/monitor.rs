@@ -0,0 +1,416 @@
+//! Change notifications, an `ip monitor` equivalent
+//!
+//! # Example
+//! ```ignore
+//! use pnetlink::packet::route::RtnlGroup;
+//! use pnetlink::packet::route::monitor::{Monitor,Event};
+//!
+//! let monitor = Monitor::new(&[RtnlGroup::Link, RtnlGroup::Ipv4Ifaddr])?;
+//! for event in monitor {
+//!     match event? {
+//!         Event::NewLink(link) => ...,
+//!         _ => {},
+//!     }
+//! }
+//! ```
+use packet::route::RtnlGroup;
+use packet::route::link::{Link,Links,RTM_NEWLINK,RTM_DELLINK};
+use packet::route::addr::{Addr,Addresses,RTM_NEWADDR,RTM_DELADDR};
+use packet::route::route::{Route,RTM_NEWROUTE,RTM_DELROUTE};
+use packet::route::neighbour::{Neighbour,Neighbours,RTM_NEWNEIGH,RTM_DELNEIGH};
+use packet::route::rule::{Rule,RTM_NEWRULE,RTM_DELRULE};
+use packet::netlink::{NetlinkPacket,NetlinkReader,NetlinkConnection,DumpPolicy};
+use socket::{NetlinkSocket,NetlinkProtocol,SockOpt};
+use error::{Error,Result};
+use libc::{self,c_int};
+use std::cmp;
+use std::collections::VecDeque;
+use std::io::{self,Read};
+use std::os::unix::io::{AsRawFd,RawFd};
+
+#[cfg(feature = "mio")]
+use mio::{Interest,Registry,Token};
+#[cfg(feature = "mio")]
+use mio::event::Source;
+
+/// Change notification
+#[derive(Debug)]
+pub enum Event {
+    NewLink(Link),
+    DelLink(Link),
+    NewAddr(Addr),
+    DelAddr(Addr),
+    NewRoute(Route),
+    DelRoute(Route),
+    NewNeigh(Neighbour),
+    DelNeigh(Neighbour),
+    NewRule(Rule),
+    DelRule(Rule),
+    /// Message of a type not decoded by this module
+    Other(NetlinkPacket<'static>),
+    /// Socket receive buffer overflowed, notifications were lost
+    Overflow,
+    /// Resync after `Overflow` is complete, objects not reported
+    /// since `Overflow` no longer exist
+    Resynced,
+}
+
+impl Event {
+    /// Decodes notification `pkt`
+    pub fn from_packet(pkt: NetlinkPacket<'static>) -> Result<Self> {
+        Ok(match pkt.get_kind() {
+            RTM_NEWLINK => Event::NewLink(try!(Link::from_packet(pkt))),
+            RTM_DELLINK => Event::DelLink(try!(Link::from_packet(pkt))),
+            RTM_NEWADDR => Event::NewAddr(try!(Addr::from_packet(pkt))),
+            RTM_DELADDR => Event::DelAddr(try!(Addr::from_packet(pkt))),
+            RTM_NEWROUTE => Event::NewRoute(try!(Route::from_packet(pkt))),
+            RTM_DELROUTE => Event::DelRoute(try!(Route::from_packet(pkt))),
+            RTM_NEWNEIGH => Event::NewNeigh(try!(Neighbour::from_packet(pkt))),
+            RTM_DELNEIGH => Event::DelNeigh(try!(Neighbour::from_packet(pkt))),
+            RTM_NEWRULE => Event::NewRule(try!(Rule::from_packet(pkt))),
+            RTM_DELRULE => Event::DelRule(try!(Rule::from_packet(pkt))),
+            _ => Event::Other(pkt),
+        })
+    }
+}
+
+/// Socket subscribed to RTNETLINK multicast groups
+///
+/// Iterating blocks until the next event. Switch the monitor to
+/// non-blocking mode and use `try_next` to drive it from an event loop.
+///
+/// When the socket receive buffer overflows the kernel drops
+/// notifications and `Event::Overflow` is returned. With resync enabled
+/// (see `MonitorBuilder::resync`) it is followed by the current state of
+/// all subscribed objects as `New*` events and `Event::Resynced`.
+pub struct Monitor<R: Read = NetlinkSocket> {
+    reader: NetlinkReader<R>,
+    groups: Vec<RtnlGroup>,
+    resync: Option<(NetlinkConnection, DumpPolicy)>,
+    resync_pending: bool,
+    pending: VecDeque<Event>,
+}
+
+/// Configures and opens a `Monitor`
+#[derive(Default)]
+pub struct MonitorBuilder {
+    groups: Vec<RtnlGroup>,
+    no_enobufs: bool,
+    rcvbuf: Option<usize>,
+    resync: Option<DumpPolicy>,
+}
+
+impl MonitorBuilder {
+    pub fn new() -> Self {
+        MonitorBuilder {
+            groups: vec![],
+            no_enobufs: false,
+            rcvbuf: None,
+            resync: None,
+        }
+    }
+
+    /// Subscribes to `group`
+    pub fn group(mut self, group: RtnlGroup) -> Self {
+        self.groups.push(group);
+        self
+    }
+
+    /// Subscribes to all of `groups`
+    pub fn groups(mut self, groups: &[RtnlGroup]) -> Self {
+        self.groups.extend_from_slice(groups);
+        self
+    }
+
+    /// Do not report overflows (`SockOpt::NoEnobufs`), lost
+    /// notifications go unnoticed
+    pub fn no_enobufs(mut self, no_enobufs: bool) -> Self {
+        self.no_enobufs = no_enobufs;
+        self
+    }
+
+    /// Socket receive buffer size, the `rmem_max` limit is bypassed
+    /// with SO_RCVBUFFORCE when the process has CAP_NET_ADMIN
+    pub fn rcvbuf(mut self, size: usize) -> Self {
+        self.rcvbuf = Some(size);
+        self
+    }
+
+    /// Re-dump subscribed objects after an overflow, dumps are
+    /// restarted according to `policy` if interrupted
+    pub fn resync(mut self, policy: DumpPolicy) -> Self {
+        self.resync = Some(policy);
+        self
+    }
+
+    pub fn build(self) -> Result<Monitor> {
+        let mut sock = try!(NetlinkSocket::bind(NetlinkProtocol::Route, 0));
+        for group in &self.groups {
+            try!(sock.join_group(*group));
+        }
+        if self.no_enobufs {
+            try!(sock.setsockopt(SockOpt::NoEnobufs, true));
+        }
+        if let Some(size) = self.rcvbuf {
+            let size = cmp::min(size, c_int::max_value() as usize) as c_int;
+            if sock.setrcvbuf_force(size).is_err() {
+                try!(sock.setrcvbuf(size));
+            }
+        }
+        let resync = match self.resync {
+            Some(policy) => Some((try!(NetlinkConnection::new()), policy)),
+            None => None,
+        };
+        Ok(Monitor {
+            reader: NetlinkReader::new(sock),
+            groups: self.groups,
+            resync: resync,
+            resync_pending: false,
+            pending: VecDeque::new(),
+        })
+    }
+}
+
+impl Monitor {
+    /// Opens a socket subscribed to `groups`, see `MonitorBuilder`
+    /// for more options
+    pub fn new(groups: &[RtnlGroup]) -> Result<Self> {
+        MonitorBuilder::new().groups(groups).build()
+    }
+
+    /// Subscribes to one more group
+    pub fn join_group(&mut self, group: RtnlGroup) -> Result<()> {
+        try!(self.reader.get_mut().join_group(group));
+        self.groups.push(group);
+        Ok(())
+    }
+
+    /// Unsubscribes from `group`
+    pub fn leave_group(&mut self, group: RtnlGroup) -> Result<()> {
+        try!(self.reader.get_mut().leave_group(group));
+        self.groups.retain(|g| *g != group);
+        Ok(())
+    }
+
+    /// Switch the socket to non-blocking mode, see `try_next`
+    pub fn set_nonblocking(&mut self, nonblocking: bool) -> Result<()> {
+        self.reader.get_mut().set_nonblocking(nonblocking).map_err(Error::from)
+    }
+}
+
+impl<R: Read> Monitor<R> {
+    /// Blocks until the next event
+    pub fn next_event(&mut self) -> Result<Event> {
+        if self.resync_pending {
+            try!(self.resync());
+        }
+        if let Some(event) = self.pending.pop_front() {
+            return Ok(event);
+        }
+        loop {
+            match self.reader.read_netlink() {
+                Ok(Some(pkt)) => return Event::from_packet(pkt),
+                // the reader stops after errors, notifications keep coming
+                Ok(None) => self.reader.reset(),
+                Err(Error::Io(ref e)) if e.raw_os_error() == Some(libc::ENOBUFS) => {
+                    self.reader.reset();
+                    self.resync_pending = self.resync.is_some();
+                    return Ok(Event::Overflow);
+                },
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Returns the next event or `None` if the non-blocking
+    /// socket has nothing queued
+    pub fn try_next(&mut self) -> Result<Option<Event>> {
+        match self.next_event() {
+            Ok(event) => Ok(Some(event)),
+            Err(Error::Io(ref e)) if e.kind() == io::ErrorKind::WouldBlock => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Dumps subscribed objects into `pending`
+    fn resync(&mut self) -> Result<()> {
+        let has = |group| self.groups.contains(&group);
+        let (links, neighbours, rules) = (has(RtnlGroup::Link), has(RtnlGroup::Neigh),
+            has(RtnlGroup::Ipv4Rule) || has(RtnlGroup::Ipv6Rule));
+        let addr_families = families(has(RtnlGroup::Ipv4Ifaddr), has(RtnlGroup::Ipv6Ifaddr));
+        let route_families = families(has(RtnlGroup::Ipv4Route), has(RtnlGroup::Ipv6Route));
+        let (ref mut conn, policy) = *self.resync.as_mut().unwrap();
+        let mut events = VecDeque::new();
+        if links {
+            for link in try!(conn.snapshot_links(policy)) {
+                events.push_back(Event::NewLink(link));
+            }
+        }
+        if !addr_families.is_empty() {
+            for addr in try!(conn.snapshot_addrs(None, policy)) {
+                if addr_families.contains(&addr.get_family()) {
+                    events.push_back(Event::NewAddr(addr));
+                }
+            }
+        }
+        if !route_families.is_empty() {
+            for route in try!(Route::snapshot_routes(conn, policy)) {
+                if route_families.contains(&route.view().get_family()) {
+                    events.push_back(Event::NewRoute(route));
+                }
+            }
+        }
+        if neighbours {
+            for neighbour in try!(conn.snapshot_neighbours(None, policy)) {
+                events.push_back(Event::NewNeigh(neighbour));
+            }
+        }
+        if rules {
+            for rule in try!(Rule::snapshot_rules(conn, policy)) {
+                events.push_back(Event::NewRule(rule));
+            }
+        }
+        events.push_back(Event::Resynced);
+        self.pending.extend(events);
+        self.resync_pending = false;
+        Ok(())
+    }
+}
+
+/// Address families matching IPv4 and IPv6 groups
+fn families(ipv4: bool, ipv6: bool) -> Vec<u8> {
+    let mut families = vec![];
+    if ipv4 {
+        families.push(libc::AF_INET as u8);
+    }
+    if ipv6 {
+        families.push(libc::AF_INET6 as u8);
+    }
+    families
+}
+
+impl<R: Read> Iterator for Monitor<R> {
+    type Item = Result<Event>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.next_event())
+    }
+}
+
+impl AsRawFd for Monitor {
+    fn as_raw_fd(&self) -> RawFd {
+        self.reader.get_ref().as_raw_fd()
+    }
+}
+
+#[cfg(feature = "mio")]
+impl Source for Monitor {
+    fn register(&mut self, registry: &Registry, token: Token, interests: Interest) -> io::Result<()> {
+        self.reader.get_mut().register(registry, token, interests)
+    }
+
+    fn reregister(&mut self, registry: &Registry, token: Token, interests: Interest) -> io::Result<()> {
+        self.reader.get_mut().reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        self.reader.get_mut().deregister(registry)
+    }
+}
+
+#[test]
+fn decode_events() {
+    use packet::netlink::{NetlinkRequestBuilder,NetlinkMsgFlags};
+    use packet::route::link_message;
+
+    let pkt = link_message(RTM_DELLINK, 7);
+    match Event::from_packet(pkt).unwrap() {
+        Event::DelLink(link) => assert_eq!(link.get_index(), 7),
+        event => panic!("unexpected {:?}", event),
+    }
+    let pkt = NetlinkRequestBuilder::new(RTM_NEWADDR, NetlinkMsgFlags::empty()).build();
+    assert!(Event::from_packet(pkt).is_err());
+    let pkt = NetlinkRequestBuilder::new(99, NetlinkMsgFlags::empty()).build();
+    assert!(match Event::from_packet(pkt).unwrap() { Event::Other(_) => true, _ => false });
+}
+
+#[test]
+fn address_families() {
+    assert_eq!(families(true, false), vec![libc::AF_INET as u8]);
+    assert_eq!(families(false, true), vec![libc::AF_INET6 as u8]);
+    assert_eq!(families(true, true), vec![libc::AF_INET as u8, libc::AF_INET6 as u8]);
+    assert!(families(false, false).is_empty());
+}
+
+/// Replays datagrams and errors, then has nothing queued
+#[cfg(test)]
+struct Datagrams(VecDeque<io::Result<Vec<u8>>>);
+
+#[cfg(test)]
+impl Read for Datagrams {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.0.pop_front() {
+            Some(Ok(data)) => {
+                buf[..data.len()].copy_from_slice(&data);
+                Ok(data.len())
+            },
+            Some(Err(e)) => Err(e),
+            None => Err(io::Error::new(io::ErrorKind::WouldBlock, "empty")),
+        }
+    }
+}
+
+#[cfg(test)]
+fn overflowing_monitor(groups: &[RtnlGroup], resync: bool) -> Monitor<Datagrams> {
+    use packet::route::link_message;
+    use pnet::packet::Packet;
+
+    let link = |kind, index| Ok(link_message(kind, index).packet().to_owned());
+    let datagrams = vec![
+        link(RTM_NEWLINK, 100),
+        Err(io::Error::from_raw_os_error(libc::ENOBUFS)),
+        link(RTM_DELLINK, 100),
+    ];
+    Monitor {
+        reader: NetlinkReader::new(Datagrams(datagrams.into_iter().collect())),
+        groups: groups.to_vec(),
+        resync: if resync { Some((NetlinkConnection::new().unwrap(), DumpPolicy::Retry(10))) } else { None },
+        resync_pending: false,
+        pending: VecDeque::new(),
+    }
+}
+
+#[test]
+fn overflow_without_resync() {
+    let mut monitor = overflowing_monitor(&[RtnlGroup::Link], false);
+    assert!(match monitor.try_next().unwrap() { Some(Event::NewLink(ref link)) => link.get_index() == 100, _ => false });
+    match monitor.try_next().unwrap() {
+        Some(Event::Overflow) => {},
+        event => panic!("unexpected {:?}", event),
+    }
+    // notifications keep coming after the overflow
+    assert!(match monitor.try_next().unwrap() { Some(Event::DelLink(ref link)) => link.get_index() == 100, _ => false });
+    assert!(monitor.try_next().unwrap().is_none());
+}
+
+#[test]
+fn overflow_resync() {
+    let mut monitor = overflowing_monitor(&[RtnlGroup::Link], true);
+    assert!(match monitor.try_next().unwrap() { Some(Event::NewLink(ref link)) => link.get_index() == 100, _ => false });
+    match monitor.try_next().unwrap() {
+        Some(Event::Overflow) => {},
+        event => panic!("unexpected {:?}", event),
+    }
+    // the dump is reported before queued notifications, ending with Resynced
+    let mut dumped = vec![];
+    loop {
+        match monitor.try_next().unwrap() {
+            Some(Event::NewLink(link)) => dumped.push(link.get_index()),
+            Some(Event::Resynced) => break,
+            event => panic!("unexpected {:?}", event),
+        }
+    }
+    assert!(dumped.contains(&1));
+    assert!(match monitor.try_next().unwrap() { Some(Event::DelLink(ref link)) => link.get_index() == 100, _ => false });
+    assert!(monitor.try_next().unwrap().is_none());
+}
@@ -8,19 +8,32 @@ use byteorder::{ByteOrder, NativeEndian};
 use packet::route::addr::Addr;
 use packet::route::{NeighbourDiscoveryPacket, MutableNeighbourDiscoveryPacket, RtAttrIterator,
                     RtAttrPacket, MutableRtAttrPacket, RtAttrMtuPacket};
-use packet::route::link::Link;
+use packet::route::{IfInfoPacket, MutableIfInfoPacket};
+use packet::route::link::{Link, IFLA_MASTER};
+use packet::route::bridge::AF_BRIDGE;
+use packet::route::nla::{Nla, NlaIterator, FromNla, emit_nla};
 use packet::netlink::{MutableNetlinkPacket, NetlinkPacket, NetlinkErrorPacket};
-use packet::netlink::{NLM_F_ACK, NLM_F_REQUEST, NLM_F_DUMP, NLM_F_MATCH, NLM_F_EXCL, NLM_F_CREATE};
+use packet::netlink::{NLM_F_ACK, NLM_F_REQUEST, NLM_F_DUMP, NLM_F_MATCH, NLM_F_EXCL, NLM_F_CREATE,
+                      NLM_F_REPLACE};
+use packet::netlink::NetlinkMsgFlags;
 use packet::netlink::{NLMSG_NOOP, NLMSG_ERROR, NLMSG_DONE, NLMSG_OVERRUN};
 use packet::netlink::{NetlinkBufIterator, NetlinkReader, NetlinkRequestBuilder};
 use ::socket::{NetlinkSocket, NetlinkProtocol};
-use packet::netlink::NetlinkConnection;
+use packet::netlink::{NetlinkConnection, DumpPolicy};
 use packet::route::addr::IpAddr;
+use error::{Error, Result};
 use pnet::packet::MutablePacket;
 use pnet::packet::Packet;
 use pnet::packet::PacketSize;
 use pnet::util::MacAddr;
 
+#[cfg(feature = "async")]
+use packet::async_netlink::AsyncNetlinkConnection;
+#[cfg(feature = "async")]
+use futures::future::{self, BoxFuture, FutureExt, TryFutureExt};
+#[cfg(feature = "async")]
+use futures::stream::{BoxStream, StreamExt, TryStreamExt};
+
 // rt message types
 pub const RTM_NEWNEIGH: u16 = 28;
 pub const RTM_DELNEIGH: u16 = 29;
@@ -45,7 +58,11 @@ pub enum NeighbourAttributes {
 }
 
 impl From<u16> for NeighbourAttributes {
+    /// Attributes unknown to this crate map to `UNSPEC`
     fn from(val: u16) -> Self {
+        if val > NeighbourAttributes::LINK_NETNSID as u16 {
+            return NeighbourAttributes::UNSPEC;
+        }
         unsafe { mem::transmute(val) }
     }
 }
@@ -138,22 +155,138 @@ pub struct Neighbour {
     packet: NetlinkPacket<'static>,
 }
 
+/// State of a bridge forwarding database entry
+#[derive(Copy,Clone,Debug,PartialEq,Eq)]
+pub enum FdbState {
+    /// Local address of the bridge or port, never forwarded
+    Permanent,
+    /// Static entry which does not age out
+    Static,
+    /// Entry aging out like learned ones
+    Dynamic,
+}
+
+impl FdbState {
+    fn to_state(&self) -> NeighbourState {
+        match *self {
+            FdbState::Permanent => PERMANENT,
+            FdbState::Static => NOARP,
+            FdbState::Dynamic => REACHABLE,
+        }
+    }
+}
+
+/// Bridge forwarding database entry, see `Neighbours::add_fdb`
+///
+/// Entries go to the port's own table (`SELF`) unless `master`
+/// is requested, as with `bridge fdb add`.
+#[derive(Clone,Debug)]
+pub struct FdbEntry {
+    ifindex: u32,
+    ll_addr: MacAddr,
+    vlan: Option<u16>,
+    state: FdbState,
+    flags: NeighbourFlags,
+}
+
+impl FdbEntry {
+    /// Static entry for `ll_addr` on `port`
+    pub fn new(port: &Link, ll_addr: MacAddr) -> Self {
+        FdbEntry {
+            ifindex: port.get_index(),
+            ll_addr: ll_addr,
+            vlan: None,
+            state: FdbState::Static,
+            flags: NeighbourFlags::empty(),
+        }
+    }
+
+    /// Entry matching a dumped AF_BRIDGE neighbour, e.g. to delete it
+    pub fn from_neighbour(neighbour: &Neighbour) -> Result<Self> {
+        if neighbour.get_family() != AF_BRIDGE {
+            return Err(Error::UnsupportedFamily(neighbour.get_family()));
+        }
+        let ll_addr = match neighbour.get_ll_addr() {
+            Some(ll_addr) => ll_addr,
+            None => return Err(Error::Malformed("FDB entry without link layer address")),
+        };
+        let state = neighbour.get_state();
+        let mut flags = neighbour.get_flags() & (SELF | MASTER);
+        // the bridge reports its own entries without NTF_MASTER
+        if flags.is_empty() && try!(neighbour.get_master()).is_some() {
+            flags = MASTER;
+        }
+        Ok(FdbEntry {
+            ifindex: neighbour.get_ifindex(),
+            ll_addr: ll_addr,
+            vlan: try!(neighbour.get_vlan_id()),
+            state: if state.contains(PERMANENT) {
+                FdbState::Permanent
+            } else if state.contains(NOARP) {
+                FdbState::Static
+            } else {
+                FdbState::Dynamic
+            },
+            flags: flags,
+        })
+    }
+
+    pub fn vlan(mut self, vid: u16) -> Self {
+        self.vlan = Some(vid);
+        self
+    }
+
+    pub fn state(mut self, state: FdbState) -> Self {
+        self.state = state;
+        self
+    }
+
+    /// Targets the bridge's table the port is enslaved to
+    pub fn master(mut self) -> Self {
+        self.flags.insert(MASTER);
+        self
+    }
+
+    /// Targets the port's own table
+    pub fn self_(mut self) -> Self {
+        self.flags.insert(SELF);
+        self
+    }
+
+    pub fn get_ifindex(&self) -> u32 {
+        self.ifindex
+    }
+
+    pub fn get_ll_addr(&self) -> MacAddr {
+        self.ll_addr
+    }
+
+    pub fn get_vlan(&self) -> Option<u16> {
+        self.vlan
+    }
+
+    pub fn get_state(&self) -> FdbState {
+        self.state
+    }
+}
+
 pub struct NeighboursIterator<R: Read> {
     iter: NetlinkBufIterator<R>,
 }
 
 impl<R: Read> Iterator for NeighboursIterator<R> {
-    type Item = Neighbour;
+    type Item = Result<Neighbour>;
 
     fn next(&mut self) -> Option<Self::Item> {
         match self.iter.next() {
-            Some(pkt) => {
+            Some(Ok(pkt)) => {
                 let kind = pkt.get_kind();
                 if kind != RTM_NEWNEIGH {
                     return None;
                 }
-                return Some(Neighbour { packet: pkt });
+                return Some(Neighbour::from_packet(pkt));
             }
+            Some(Err(e)) => Some(Err(e)),
             None => None,
         }
     }
@@ -162,48 +295,38 @@ impl<R: Read> Iterator for NeighboursIterator<R> {
 impl ::std::fmt::Debug for Neighbour {
     fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
         let family = self.get_family();
-        write!(f,
-               "{:?}: {:?}, {:?}, {:?}, {:?}",
-               family,
-               self.get_ifindex(),
-               self.get_state(),
-               self.get_flags(),
-               self.get_type());
-        self.with_rta_iter(|mut iter| {
+        try!(write!(f,
+                    "{:?}: {:?}, {:?}, {:?}, {:?}",
+                    family,
+                    self.get_ifindex(),
+                    self.get_state(),
+                    self.get_flags(),
+                    self.get_type()));
+        self.with_rta_iter(|iter| {
             for rta in iter {
                 match NeighbourAttributes::from(rta.get_rta_type()) {
                     NeighbourAttributes::LLADDR => {
-                        let payload = rta.payload();
-                        let mac_addr = MacAddr::new(payload[0],
-                                                    payload[1],
-                                                    payload[2],
-                                                    payload[3],
-                                                    payload[4],
-                                                    payload[5]);
-                        write!(f, " lladdr: {:?}", mac_addr);
+                        match MacAddr::from_nla(rta.payload()) {
+                            Ok(mac_addr) => try!(write!(f, " lladdr: {:?}", mac_addr)),
+                            Err(_) => try!(write!(f, " lladdr: {:?}", rta.payload())),
+                        }
                     }
                     NeighbourAttributes::VLAN => {
-                        write!(f, " vlan id: {:?}", rta.payload());
+                        try!(write!(f, " vlan id: {:?}", rta.payload()));
                     }
                     NeighbourAttributes::DST => {
-                        match rta.get_rta_len() {
-                            // 4 for the rta header, then 4 or 16.
-                            8 | 20 => {
-                                let addr = Addr::ip_from_family_and_bytes(family, rta.payload());
-                                write!(f, " {:?}", addr);
-                            }
-                            l => {
-                                write!(f, "unknown address length {:?}", l);
-                            }
+                        match Addr::ip_from_family_and_bytes(family, rta.payload()) {
+                            Ok(addr) => try!(write!(f, " {:?}", addr)),
+                            Err(e) => try!(write!(f, " {}", e)),
                         }
                     }
                     _ => {
-                        write!(f, " unknown attribute {:?}", rta);
+                        try!(write!(f, " unknown attribute {:?}", rta));
                     }
                 }
             }
-        });
-        Ok(())
+            Ok(())
+        })
     }
 }
 
@@ -213,7 +336,24 @@ pub trait Neighbours
     /// iterate over neighbours
     fn iter_neighbours(&mut self,
                        link: Option<&Link>)
-                       -> io::Result<Box<NeighboursIterator<&mut Self>>>;
+                       -> Result<Box<NeighboursIterator<&mut Self>>>;
+    /// returns a consistent snapshot of neighbours, see `DumpPolicy`
+    fn snapshot_neighbours(&mut self,
+                           link: Option<&Link>,
+                           policy: DumpPolicy)
+                           -> Result<Vec<Neighbour>>;
+    /// returns bridge FDB entries, optionally only those of `bridge` or `port`
+    fn snapshot_fdb(&mut self,
+                    bridge: Option<&Link>,
+                    port: Option<&Link>,
+                    policy: DumpPolicy)
+                    -> Result<Vec<Neighbour>>;
+    /// add bridge FDB entry, fails if it exists
+    fn add_fdb(&mut self, entry: FdbEntry) -> Result<()>;
+    /// add bridge FDB entry or replace the existing one
+    fn replace_fdb(&mut self, entry: FdbEntry) -> Result<()>;
+    /// delete bridge FDB entry
+    fn delete_fdb(&mut self, entry: FdbEntry) -> Result<()>;
     // Not implemented yet.
     // delete neighbour
     // fn delete_neighbour(&mut self, neighbour: Neighbour) -> io::Result<()>;
@@ -225,23 +365,52 @@ pub trait Neighbours
 impl Neighbours for NetlinkConnection {
     fn iter_neighbours(&mut self,
                        link: Option<&Link>)
-                       -> io::Result<Box<NeighboursIterator<&mut Self>>> {
-        // NB: This should be a IfInfoPacket because - well see rtnetlink.c in Linux - but they pun
-        // successfully.
-        //
-        let req = NetlinkRequestBuilder::new(RTM_GETNEIGH, NLM_F_DUMP)
-            .append(match link {
-                    Some(link) => {
-                        NeighbourDiscoveryPacketBuilder::new().set_ifindex(link.get_index())
-                    }
-                    _ => NeighbourDiscoveryPacketBuilder::new(),
-                }
-                .build())
-            .build();
-        try!(self.write(req.packet()));
-        let reader = NetlinkReader::new(self);
+                       -> Result<Box<NeighboursIterator<&mut Self>>> {
+        let req = dump_neighbours_request(link);
+        let reader = try!(self.send(req));
         Ok(Box::new(NeighboursIterator { iter: reader.into_iter() }))
     }
+
+    fn snapshot_neighbours(&mut self,
+                           link: Option<&Link>,
+                           policy: DumpPolicy)
+                           -> Result<Vec<Neighbour>> {
+        let pkts = try!(self.dump(&dump_neighbours_request(link), policy));
+        pkts.into_iter()
+            .filter(|pkt| pkt.get_kind() == RTM_NEWNEIGH)
+            .map(Neighbour::from_packet)
+            .collect()
+    }
+
+    fn snapshot_fdb(&mut self,
+                    bridge: Option<&Link>,
+                    port: Option<&Link>,
+                    policy: DumpPolicy)
+                    -> Result<Vec<Neighbour>> {
+        let pkts = try!(self.dump(&dump_fdb_request(bridge, port), policy));
+        pkts.into_iter()
+            .filter(|pkt| pkt.get_kind() == RTM_NEWNEIGH)
+            .map(Neighbour::from_packet)
+            .collect()
+    }
+
+    fn add_fdb(&mut self, entry: FdbEntry) -> Result<()> {
+        let req = new_fdb_request(entry, false);
+        let reader = try!(self.send(req));
+        reader.read_to_end()
+    }
+
+    fn replace_fdb(&mut self, entry: FdbEntry) -> Result<()> {
+        let req = new_fdb_request(entry, true);
+        let reader = try!(self.send(req));
+        reader.read_to_end()
+    }
+
+    fn delete_fdb(&mut self, entry: FdbEntry) -> Result<()> {
+        let req = fdb_request(RTM_DELNEIGH, NetlinkMsgFlags::empty(), entry);
+        let reader = try!(self.send(req));
+        reader.read_to_end()
+    }
     // fn get_neighbour_by_index(&mut self, index: u32) -> io::Result<Option<Neighbour>> {
     // let mut req = {
     // let mut buf = vec![0; MutableNeighbourDiscoveryPacket::minimum_packet_size()];
@@ -295,7 +464,139 @@ impl Neighbours for NetlinkConnection {
     //
 }
 
+/// Async counterpart of `Neighbours`
+#[cfg(feature = "async")]
+pub trait AsyncNeighbours {
+    /// iterate over neighbours
+    fn iter_neighbours<'a>(&'a mut self,
+                           link: Option<&Link>)
+                           -> BoxFuture<'a, Result<BoxStream<'a, Result<Neighbour>>>>;
+    /// iterate over bridge FDB entries, optionally only those of `bridge` or `port`
+    fn iter_fdb<'a>(&'a mut self,
+                    bridge: Option<&Link>,
+                    port: Option<&Link>)
+                    -> BoxFuture<'a, Result<BoxStream<'a, Result<Neighbour>>>>;
+    /// add bridge FDB entry, fails if it exists
+    fn add_fdb<'a>(&'a mut self, entry: FdbEntry) -> BoxFuture<'a, Result<()>>;
+    /// add bridge FDB entry or replace the existing one
+    fn replace_fdb<'a>(&'a mut self, entry: FdbEntry) -> BoxFuture<'a, Result<()>>;
+    /// delete bridge FDB entry
+    fn delete_fdb<'a>(&'a mut self, entry: FdbEntry) -> BoxFuture<'a, Result<()>>;
+}
+
+#[cfg(feature = "async")]
+impl AsyncNeighbours for AsyncNetlinkConnection {
+    fn iter_neighbours<'a>(&'a mut self,
+                           link: Option<&Link>)
+                           -> BoxFuture<'a, Result<BoxStream<'a, Result<Neighbour>>>> {
+        self.send(dump_neighbours_request(link))
+            .map_ok(|replies| {
+                replies.try_take_while(|pkt| future::ready(Ok(pkt.get_kind() == RTM_NEWNEIGH)))
+                    .and_then(|pkt| future::ready(Neighbour::from_packet(pkt)))
+                    .boxed()
+            })
+            .boxed()
+    }
+
+    fn iter_fdb<'a>(&'a mut self,
+                    bridge: Option<&Link>,
+                    port: Option<&Link>)
+                    -> BoxFuture<'a, Result<BoxStream<'a, Result<Neighbour>>>> {
+        self.send(dump_fdb_request(bridge, port))
+            .map_ok(|replies| {
+                replies.try_take_while(|pkt| future::ready(Ok(pkt.get_kind() == RTM_NEWNEIGH)))
+                    .and_then(|pkt| future::ready(Neighbour::from_packet(pkt)))
+                    .boxed()
+            })
+            .boxed()
+    }
+
+    fn add_fdb<'a>(&'a mut self, entry: FdbEntry) -> BoxFuture<'a, Result<()>> {
+        self.send_ack(new_fdb_request(entry, false))
+    }
+
+    fn replace_fdb<'a>(&'a mut self, entry: FdbEntry) -> BoxFuture<'a, Result<()>> {
+        self.send_ack(new_fdb_request(entry, true))
+    }
+
+    fn delete_fdb<'a>(&'a mut self, entry: FdbEntry) -> BoxFuture<'a, Result<()>> {
+        self.send_ack(fdb_request(RTM_DELNEIGH, NetlinkMsgFlags::empty(), entry))
+    }
+}
+
+fn dump_neighbours_request(link: Option<&Link>) -> NetlinkPacket<'static> {
+    // NB: This should be a IfInfoPacket because - well see rtnetlink.c in Linux - but they pun
+    // successfully.
+    //
+    NetlinkRequestBuilder::new(RTM_GETNEIGH, NLM_F_DUMP)
+        .append(match link {
+                Some(link) => {
+                    NeighbourDiscoveryPacketBuilder::new().set_ifindex(link.get_index())
+                }
+                _ => NeighbourDiscoveryPacketBuilder::new(),
+            }
+            .build())
+        .build()
+}
+
+fn dump_fdb_request(bridge: Option<&Link>, port: Option<&Link>) -> NetlinkPacket<'static> {
+    // The kernel parses FDB dump filters as ifinfomsg followed by IFLA_MASTER
+    let mut data = vec![0; MutableIfInfoPacket::minimum_packet_size()];
+    {
+        let mut ifinfo = MutableIfInfoPacket::new(&mut data).unwrap();
+        ifinfo.set_family(AF_BRIDGE);
+        ifinfo.set_index(port.map_or(0, |port| port.get_index()));
+    }
+    if let Some(bridge) = bridge {
+        emit_nla(&mut data, IFLA_MASTER, &bridge.get_index());
+    }
+    NetlinkRequestBuilder::new(RTM_GETNEIGH, NLM_F_DUMP)
+        .append(IfInfoPacket::owned(data).unwrap())
+        .build()
+}
+
+/// RTM_NEWNEIGH adding `entry`, replacing an existing one if `replace`
+fn new_fdb_request(entry: FdbEntry, replace: bool) -> NetlinkPacket<'static> {
+    let flags = if replace {
+        NLM_F_CREATE | NLM_F_REPLACE
+    } else {
+        NLM_F_CREATE | NLM_F_EXCL
+    };
+    fdb_request(RTM_NEWNEIGH, flags, entry)
+}
+
+fn fdb_request(kind: u16, flags: NetlinkMsgFlags, entry: FdbEntry) -> NetlinkPacket<'static> {
+    let mut ndm_flags = entry.flags;
+    if ndm_flags.is_empty() {
+        ndm_flags = SELF;
+    }
+    let mut ndm = NeighbourDiscoveryPacketBuilder::new()
+        .set_family(AF_BRIDGE)
+        .set_ifindex(entry.ifindex)
+        .set_state(entry.state.to_state())
+        .set_flags(ndm_flags)
+        .nla(NeighbourAttributes::LLADDR as u16, entry.ll_addr);
+    if let Some(vid) = entry.vlan {
+        ndm = ndm.nla(NeighbourAttributes::VLAN as u16, vid);
+    }
+    NetlinkRequestBuilder::new(kind, flags | NLM_F_ACK)
+        .append(ndm.build())
+        .build()
+}
+
 impl Neighbour {
+    /// Wraps RTM_NEWNEIGH or RTM_DELNEIGH message
+    pub fn from_packet(packet: NetlinkPacket<'static>) -> Result<Self> {
+        let kind = packet.get_kind();
+        if kind != RTM_NEWNEIGH && kind != RTM_DELNEIGH {
+            return Err(Error::Malformed("not a neighbour message"));
+        }
+        if NeighbourDiscoveryPacket::new(packet.payload()).is_none() {
+            return Err(Error::Truncated);
+        }
+        Ok(Neighbour { packet: packet })
+    }
+
     pub fn get_family(&self) -> u8 {
         self.with_neighbour(|neigh| neigh.get_family())
     }
@@ -316,43 +617,30 @@ impl Neighbour {
         self.with_neighbour(|neigh| neigh.get_type_())
     }
 
-    pub fn get_destination(&self) -> Option<IpAddr> {
+    pub fn get_destination(&self) -> Result<Option<IpAddr>> {
         let family = self.get_family();
-        let rta_lookup = self.with_rta(NeighbourAttributes::DST, |rta| {
-                match rta.get_rta_len() {
-                    // 4 for the rta header, then 4 or 16.
-                    8 | 20 => {
-                        let addr = Addr::ip_from_family_and_bytes(family, rta.payload());
-                        Some(addr)
-                    },
-                    l => {
-                        // Perhaps this should return Result<> ?
-                        println!("unknown address length {:?}", l);
-                        None
-                    }
-                }
-            });
-        match rta_lookup {
-            Some(result) => result,
-            None => None
-        }
+        self.with_rta(NeighbourAttributes::DST,
+                      |rta| Addr::ip_from_family_and_bytes(family, rta.payload()))
+            .map_or(Ok(None), |addr| addr.map(Some))
     }
 
+    /// Link layer address, `None` if missing or not an Ethernet address
     pub fn get_ll_addr(&self) -> Option<MacAddr> {
-        self.with_rta(NeighbourAttributes::LLADDR, |rta| {
-            let payload = rta.payload();
-            MacAddr::new(payload[0],
-                         payload[1],
-                         payload[2],
-                         payload[3],
-                         payload[4],
-                         payload[5])
-        })
+        self.get_nla(NeighbourAttributes::LLADDR as u16).unwrap_or(None)
     }
 
-    pub fn get_vlan_id(&self) -> Option<u16> {
-        self.with_rta(NeighbourAttributes::VLAN,
-                      |rta| NativeEndian::read_u16(rta.payload()))
+    pub fn get_vlan_id(&self) -> Result<Option<u16>> {
+        self.get_nla(NeighbourAttributes::VLAN as u16)
+    }
+
+    /// Index of the bridge an FDB entry belongs to
+    pub fn get_master(&self) -> Result<Option<u32>> {
+        self.get_nla(NeighbourAttributes::MASTER as u16)
+    }
+
+    /// Decodes attribute `kind`, `None` if it is missing
+    pub fn get_nla<T: FromNla>(&self, kind: u16) -> Result<Option<T>> {
+        self.with_neighbour(|neigh| NlaIterator::new(neigh.payload()).get(kind))
     }
 
     // helper methods
@@ -465,6 +753,12 @@ impl NeighbourDiscoveryPacketBuilder {
         self
     }
 
+    /// Appends attribute `kind` with value `value`
+    pub fn nla<T: Nla>(mut self, kind: u16, value: T) -> Self {
+        emit_nla(&mut self.data, kind, &value);
+        self
+    }
+
     pub fn build(self) -> NeighbourDiscoveryPacket<'static> {
         NeighbourDiscoveryPacket::owned(self.data).unwrap()
     }
@@ -476,9 +770,9 @@ mod tests {
     fn dump_neighbours() {
         use ::packet::netlink::NetlinkConnection;
         use ::packet::route::neighbour::{Neighbour, Neighbours};
-        let mut conn = NetlinkConnection::new();
+        let mut conn = NetlinkConnection::new().unwrap();
         for neighbour in conn.iter_neighbours(None).unwrap() {
-            Neighbour::dump_neighbour(neighbour.packet.get_packet());
+            Neighbour::dump_neighbour(neighbour.unwrap().packet);
         }
     }
 
@@ -488,13 +782,103 @@ mod tests {
         use ::packet::route::link::{Link, Links};
         use ::packet::route::neighbour::{Neighbour, Neighbours};
 
-        let mut conn = NetlinkConnection::new();
+        let mut conn = NetlinkConnection::new().unwrap();
         let lo0 = conn.get_link_by_name("lo").unwrap().unwrap();
         for neighbour in conn.iter_neighbours(Some(&lo0)).unwrap() {
-            Neighbour::dump_neighbour(neighbour.packet.get_packet());
+            Neighbour::dump_neighbour(neighbour.unwrap().packet);
         }
     }
 
+    #[test]
+    fn dump_fdb_request_filter() {
+        use ::packet::netlink::{NLM_F_REQUEST, NLM_F_DUMP};
+        use ::packet::route::{link_message, IfInfoPacket};
+        use ::packet::route::bridge::AF_BRIDGE;
+        use ::packet::route::link::{Link, IFLA_MASTER, RTM_NEWLINK};
+        use ::packet::route::neighbour::{dump_fdb_request, RTM_GETNEIGH};
+        use ::packet::route::nla::NlaIterator;
+        use pnet::packet::Packet;
+
+        let bridge = Link::from_packet(link_message(RTM_NEWLINK, 3)).unwrap();
+        let port = Link::from_packet(link_message(RTM_NEWLINK, 5)).unwrap();
+        let req = dump_fdb_request(Some(&bridge), Some(&port));
+        assert_eq!(req.get_kind(), RTM_GETNEIGH);
+        assert_eq!(req.get_flags(), NLM_F_REQUEST | NLM_F_DUMP);
+        let ifi = IfInfoPacket::new(req.payload()).unwrap();
+        assert_eq!((ifi.get_family(), ifi.get_index()), (AF_BRIDGE, 5));
+        assert_eq!(NlaIterator::new(ifi.payload()).get::<u32>(IFLA_MASTER).unwrap(), Some(3));
+
+        let req = dump_fdb_request(None, None);
+        let ifi = IfInfoPacket::new(req.payload()).unwrap();
+        assert_eq!(ifi.get_index(), 0);
+        assert_eq!(NlaIterator::new(ifi.payload()).count(), 0);
+    }
+
+    #[test]
+    fn fdb_request_entry() {
+        use ::packet::netlink::{NetlinkPacket, NetlinkMsgFlags};
+        use ::packet::netlink::{NLM_F_REQUEST, NLM_F_ACK, NLM_F_CREATE, NLM_F_EXCL, NLM_F_REPLACE};
+        use ::packet::route::link_message;
+        use ::packet::route::bridge::AF_BRIDGE;
+        use ::packet::route::link::{Link, RTM_NEWLINK};
+        use ::packet::route::neighbour::*;
+        use pnet::util::MacAddr;
+
+        let port = Link::from_packet(link_message(RTM_NEWLINK, 5)).unwrap();
+        let mac = MacAddr::new(2, 0, 0, 0, 0, 1);
+        let parse = |req: NetlinkPacket<'static>| Neighbour::from_packet(req).unwrap();
+
+        let req = new_fdb_request(FdbEntry::new(&port, mac).vlan(10), false);
+        assert_eq!((req.get_kind(), req.get_flags()),
+                   (RTM_NEWNEIGH, NLM_F_REQUEST | NLM_F_ACK | NLM_F_CREATE | NLM_F_EXCL));
+        let neighbour = parse(req);
+        assert_eq!((neighbour.get_family(), neighbour.get_ifindex()), (AF_BRIDGE, 5));
+        assert_eq!(neighbour.get_ll_addr(), Some(mac));
+        assert_eq!(neighbour.get_vlan_id().unwrap(), Some(10));
+        // SELF unless asked otherwise, static entries are NOARP
+        assert_eq!(neighbour.get_flags(), SELF);
+        assert_eq!(neighbour.get_state(), NOARP);
+
+        let entry = FdbEntry::new(&port, mac).master().state(FdbState::Permanent);
+        let req = new_fdb_request(entry, true);
+        assert_eq!(req.get_flags(), NLM_F_REQUEST | NLM_F_ACK | NLM_F_CREATE | NLM_F_REPLACE);
+        let neighbour = parse(req);
+        assert_eq!(neighbour.get_flags(), MASTER);
+        assert_eq!(neighbour.get_state(), PERMANENT);
+        assert_eq!(neighbour.get_vlan_id().unwrap(), None);
+
+        let entry = FdbEntry::new(&port, mac).state(FdbState::Dynamic);
+        let req = fdb_request(RTM_DELNEIGH, NetlinkMsgFlags::empty(), entry);
+        assert_eq!((req.get_kind(), req.get_flags()), (RTM_DELNEIGH, NLM_F_REQUEST | NLM_F_ACK));
+        assert_eq!(parse(req).get_state(), REACHABLE);
+    }
+
+    #[test]
+    fn fdb_entry_from_neighbour() {
+        use ::packet::netlink::NetlinkMsgFlags;
+        use ::packet::route::message;
+        use ::packet::route::bridge::AF_BRIDGE;
+        use ::packet::route::neighbour::*;
+        use pnet::util::MacAddr;
+
+        let mac = MacAddr::new(2, 0, 0, 0, 0, 1);
+        let ndm = NeighbourDiscoveryPacketBuilder::new()
+            .set_family(AF_BRIDGE)
+            .set_ifindex(5)
+            .set_state(PERMANENT)
+            .nla(NeighbourAttributes::LLADDR as u16, mac)
+            .nla(NeighbourAttributes::VLAN as u16, 10u16)
+            .nla(NeighbourAttributes::MASTER as u16, 3u32);
+        let neighbour = Neighbour::from_packet(message(RTM_NEWNEIGH, ndm.build())).unwrap();
+        assert_eq!(neighbour.get_master().unwrap(), Some(3));
+        let entry = FdbEntry::from_neighbour(&neighbour).unwrap();
+        assert_eq!((entry.get_ifindex(), entry.get_ll_addr()), (5, mac));
+        assert_eq!((entry.get_vlan(), entry.get_state()), (Some(10), FdbState::Permanent));
+        // reported by the bridge without NTF_MASTER, deleting must target it
+        let req = fdb_request(RTM_DELNEIGH, NetlinkMsgFlags::empty(), entry);
+        assert_eq!(Neighbour::from_packet(req).unwrap().get_flags(), MASTER);
+    }
+
     //    Not implemented yet.
     //    #[test]
     //    // root permissions required
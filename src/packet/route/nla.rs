@@ -0,0 +1,473 @@
+//! Typed netlink attributes
+//!
+//! `Nla` values are encoded with `NlaBuilder`, which takes care of
+//! attribute headers and alignment, nested lists are builders themselves.
+//! `NlaIterator` walks received attributes and `FromNla` decodes them.
+//!
+//! # Example
+//! ```ignore
+//! let linkinfo = NlaBuilder::new()
+//!     .append(IFLA_INFO_KIND, "dummy");
+//! let attrs = NlaBuilder::new()
+//!     .append(IFLA_IFNAME, "dummy0")
+//!     .append(IFLA_LINKINFO, linkinfo)
+//!     .build();
+//! for nla in NlaIterator::new(&attrs) {
+//!     if nla.kind() == IFLA_IFNAME {
+//!         let name: String = try!(nla.get());
+//!     }
+//! }
+//! ```
+use packet::route::{RtAttrPacket,MutableRtAttrPacket};
+use packet::route::addr::IpAddr;
+use error::{Error,Result};
+use pnet::util::MacAddr;
+use byteorder::{ByteOrder,NativeEndian};
+use std::net::{Ipv4Addr,Ipv6Addr};
+
+/// Attribute carries nested attributes
+pub const NLA_F_NESTED: u16 = 0x8000;
+/// Attribute value is in network byte order
+pub const NLA_F_NET_BYTEORDER: u16 = 0x4000;
+/// Mask to get attribute type without flags
+pub const NLA_TYPE_MASK: u16 = !(NLA_F_NESTED | NLA_F_NET_BYTEORDER);
+
+/// Value which can be encoded as an attribute payload
+pub trait Nla {
+    /// Payload length without header and padding
+    fn value_len(&self) -> usize;
+    /// Writes payload into `buf` which is exactly `value_len()` bytes long
+    fn emit_value(&self, buf: &mut [u8]);
+}
+
+/// Value which can be decoded from an attribute payload
+pub trait FromNla: Sized {
+    fn from_nla(payload: &[u8]) -> Result<Self>;
+}
+
+/// Appends attribute `kind` with value `value` to `data`, including padding
+///
+/// # Panics
+///
+/// If the attribute is longer than 65535 bytes, its length field can't hold it.
+pub fn emit_nla<T: Nla + ?Sized>(data: &mut Vec<u8>, kind: u16, value: &T) {
+    let header_len = RtAttrPacket::minimum_packet_size();
+    let len = header_len + value.value_len();
+    assert!(len <= u16::MAX as usize, "netlink attribute {} is {} bytes long", kind, len);
+    let start = data.len();
+    data.resize(start + ::util::align(len), 0);
+    {
+        let mut rta = MutableRtAttrPacket::new(&mut data[start..]).unwrap();
+        rta.set_rta_len(len as u16);
+        rta.set_rta_type(kind);
+    }
+    value.emit_value(&mut data[start + header_len..start + len]);
+}
+
+impl<'a, T: Nla + ?Sized> Nla for &'a T {
+    fn value_len(&self) -> usize {
+        (**self).value_len()
+    }
+
+    fn emit_value(&self, buf: &mut [u8]) {
+        (**self).emit_value(buf)
+    }
+}
+
+impl Nla for u8 {
+    fn value_len(&self) -> usize { 1 }
+
+    fn emit_value(&self, buf: &mut [u8]) {
+        buf[0] = *self;
+    }
+}
+
+impl Nla for u16 {
+    fn value_len(&self) -> usize { 2 }
+
+    fn emit_value(&self, buf: &mut [u8]) {
+        NativeEndian::write_u16(buf, *self);
+    }
+}
+
+impl Nla for u32 {
+    fn value_len(&self) -> usize { 4 }
+
+    fn emit_value(&self, buf: &mut [u8]) {
+        NativeEndian::write_u32(buf, *self);
+    }
+}
+
+impl Nla for u64 {
+    fn value_len(&self) -> usize { 8 }
+
+    fn emit_value(&self, buf: &mut [u8]) {
+        NativeEndian::write_u64(buf, *self);
+    }
+}
+
+impl Nla for i32 {
+    fn value_len(&self) -> usize { 4 }
+
+    fn emit_value(&self, buf: &mut [u8]) {
+        NativeEndian::write_i32(buf, *self);
+    }
+}
+
+/// Strings are NUL terminated
+impl Nla for str {
+    fn value_len(&self) -> usize {
+        self.len() + 1
+    }
+
+    fn emit_value(&self, buf: &mut [u8]) {
+        buf[..self.len()].copy_from_slice(self.as_bytes());
+        buf[self.len()] = 0;
+    }
+}
+
+impl Nla for String {
+    fn value_len(&self) -> usize {
+        self[..].value_len()
+    }
+
+    fn emit_value(&self, buf: &mut [u8]) {
+        self[..].emit_value(buf)
+    }
+}
+
+/// Raw bytes are copied as is
+impl Nla for [u8] {
+    fn value_len(&self) -> usize {
+        self.len()
+    }
+
+    fn emit_value(&self, buf: &mut [u8]) {
+        buf.copy_from_slice(self);
+    }
+}
+
+impl Nla for Vec<u8> {
+    fn value_len(&self) -> usize {
+        self.len()
+    }
+
+    fn emit_value(&self, buf: &mut [u8]) {
+        buf.copy_from_slice(self);
+    }
+}
+
+impl Nla for MacAddr {
+    fn value_len(&self) -> usize { 6 }
+
+    fn emit_value(&self, buf: &mut [u8]) {
+        buf.copy_from_slice(&[self.0, self.1, self.2, self.3, self.4, self.5]);
+    }
+}
+
+impl Nla for Ipv4Addr {
+    fn value_len(&self) -> usize { 4 }
+
+    fn emit_value(&self, buf: &mut [u8]) {
+        buf.copy_from_slice(&self.octets());
+    }
+}
+
+impl Nla for Ipv6Addr {
+    fn value_len(&self) -> usize { 16 }
+
+    fn emit_value(&self, buf: &mut [u8]) {
+        buf.copy_from_slice(&self.octets());
+    }
+}
+
+impl Nla for IpAddr {
+    fn value_len(&self) -> usize {
+        match *self {
+            IpAddr::V4(ref ip) => ip.value_len(),
+            IpAddr::V6(ref ip) => ip.value_len(),
+        }
+    }
+
+    fn emit_value(&self, buf: &mut [u8]) {
+        match *self {
+            IpAddr::V4(ref ip) => ip.emit_value(buf),
+            IpAddr::V6(ref ip) => ip.emit_value(buf),
+        }
+    }
+}
+
+/// Nested attribute list
+impl Nla for NlaBuilder {
+    fn value_len(&self) -> usize {
+        self.data.len()
+    }
+
+    fn emit_value(&self, buf: &mut [u8]) {
+        buf.copy_from_slice(&self.data);
+    }
+}
+
+/// NlaBuilder builds a list of attributes, use it as a value
+/// to build nested attributes
+#[derive(Clone,Debug,Default)]
+pub struct NlaBuilder {
+    data: Vec<u8>,
+}
+
+impl NlaBuilder {
+    /// Creates an empty attribute list
+    pub fn new() -> Self {
+        NlaBuilder { data: Vec::new() }
+    }
+
+    /// Appends attribute `kind` with value `value`
+    pub fn append<T: Nla>(mut self, kind: u16, value: T) -> Self {
+        emit_nla(&mut self.data, kind, &value);
+        self
+    }
+
+    /// Appends nested attribute `kind` marked with `NLA_F_NESTED`
+    pub fn nested(self, kind: u16, nested: NlaBuilder) -> Self {
+        self.append(kind | NLA_F_NESTED, nested)
+    }
+
+    /// Appends attribute `kind` with value `value` if it is present
+    pub fn append_opt<T: Nla>(self, kind: u16, value: Option<T>) -> Self {
+        match value {
+            Some(value) => self.append(kind, value),
+            None => self,
+        }
+    }
+
+    /// Length of the encoded attributes including padding
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns encoded attributes
+    pub fn build(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+impl FromNla for u8 {
+    fn from_nla(payload: &[u8]) -> Result<Self> {
+        try!(check_len(payload, 1));
+        Ok(payload[0])
+    }
+}
+
+impl FromNla for u16 {
+    fn from_nla(payload: &[u8]) -> Result<Self> {
+        try!(check_len(payload, 2));
+        Ok(NativeEndian::read_u16(payload))
+    }
+}
+
+impl FromNla for u32 {
+    fn from_nla(payload: &[u8]) -> Result<Self> {
+        try!(check_len(payload, 4));
+        Ok(NativeEndian::read_u32(payload))
+    }
+}
+
+impl FromNla for u64 {
+    fn from_nla(payload: &[u8]) -> Result<Self> {
+        try!(check_len(payload, 8));
+        Ok(NativeEndian::read_u64(payload))
+    }
+}
+
+impl FromNla for i32 {
+    fn from_nla(payload: &[u8]) -> Result<Self> {
+        try!(check_len(payload, 4));
+        Ok(NativeEndian::read_i32(payload))
+    }
+}
+
+/// Reads up to the first NUL, a missing terminator is tolerated
+impl FromNla for String {
+    fn from_nla(payload: &[u8]) -> Result<Self> {
+        let nla = NlaRef { kind: 0, value: payload };
+        nla.get_str().map(|s| s.to_owned())
+    }
+}
+
+impl FromNla for Vec<u8> {
+    fn from_nla(payload: &[u8]) -> Result<Self> {
+        Ok(payload.to_owned())
+    }
+}
+
+impl FromNla for MacAddr {
+    fn from_nla(payload: &[u8]) -> Result<Self> {
+        try!(check_len(payload, 6));
+        Ok(MacAddr::new(payload[0], payload[1], payload[2], payload[3], payload[4], payload[5]))
+    }
+}
+
+impl FromNla for Ipv4Addr {
+    fn from_nla(payload: &[u8]) -> Result<Self> {
+        try!(check_len(payload, 4));
+        Ok(Ipv4Addr::new(payload[0], payload[1], payload[2], payload[3]))
+    }
+}
+
+impl FromNla for Ipv6Addr {
+    fn from_nla(payload: &[u8]) -> Result<Self> {
+        try!(check_len(payload, 16));
+        let mut octets = [0; 16];
+        octets.copy_from_slice(payload);
+        Ok(Ipv6Addr::from(octets))
+    }
+}
+
+fn check_len(payload: &[u8], len: usize) -> Result<()> {
+    if payload.len() != len {
+        return Err(Error::Malformed("attribute length"));
+    }
+    Ok(())
+}
+
+/// Attribute borrowed from a received message
+#[derive(Clone,Copy,Debug)]
+pub struct NlaRef<'a> {
+    kind: u16,
+    value: &'a [u8],
+}
+
+impl<'a> NlaRef<'a> {
+    /// Attribute type without `NLA_F_*` flags
+    pub fn kind(&self) -> u16 {
+        self.kind & NLA_TYPE_MASK
+    }
+
+    /// Whether the attribute is marked as nested
+    pub fn is_nested(&self) -> bool {
+        self.kind & NLA_F_NESTED != 0
+    }
+
+    /// Payload without header and padding
+    pub fn value(&self) -> &'a [u8] {
+        self.value
+    }
+
+    /// Decodes payload as `T`
+    pub fn get<T: FromNla>(&self) -> Result<T> {
+        T::from_nla(self.value)
+    }
+
+    /// Borrows payload as a string, up to the first NUL
+    pub fn get_str(&self) -> Result<&'a str> {
+        let bytes = self.value.split(|b| *b == 0).next().unwrap_or(self.value);
+        ::std::str::from_utf8(bytes)
+            .map_err(|_| Error::Malformed("attribute string is not UTF-8"))
+    }
+
+    /// Iterates over attributes nested in the payload
+    pub fn nested(&self) -> NlaIterator<'a> {
+        NlaIterator::new(self.value)
+    }
+}
+
+/// Iterator over attributes in a buffer
+///
+/// Unlike `RtAttrIterator` it keeps the buffer's lifetime so nested
+/// attributes can be walked. Iteration stops at the first attribute
+/// whose header is inconsistent with the buffer, see `is_truncated`.
+#[derive(Clone)]
+pub struct NlaIterator<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> NlaIterator<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        NlaIterator { buf: buf }
+    }
+
+    /// Whether iteration stopped at a malformed attribute
+    pub fn is_truncated(&self) -> bool {
+        !self.buf.is_empty()
+    }
+
+    /// Finds first attribute of type `kind`
+    pub fn find_kind(self, kind: u16) -> Option<NlaRef<'a>> {
+        for nla in self {
+            if nla.kind() == kind {
+                return Some(nla);
+            }
+        }
+        None
+    }
+
+    /// Decodes first attribute of type `kind`, `None` if it is missing
+    pub fn get<T: FromNla>(self, kind: u16) -> Result<Option<T>> {
+        match self.find_kind(kind) {
+            Some(nla) => nla.get().map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Borrows first attribute of type `kind` as a string
+    pub fn get_str(self, kind: u16) -> Result<Option<&'a str>> {
+        match self.find_kind(kind) {
+            Some(nla) => nla.get_str().map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+impl<'a> Iterator for NlaIterator<'a> {
+    type Item = NlaRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let header_len = RtAttrPacket::minimum_packet_size();
+        let (len, kind) = match RtAttrPacket::new(self.buf) {
+            Some(rta) => (rta.get_rta_len() as usize, rta.get_rta_type()),
+            None => return None,
+        };
+        if len < header_len || len > self.buf.len() {
+            return None;
+        }
+        let value = &self.buf[header_len..len];
+        let next = ::std::cmp::min(::util::align(len), self.buf.len());
+        self.buf = &self.buf[next..];
+        Some(NlaRef { kind: kind, value: value })
+    }
+}
+
+#[test]
+fn build_and_parse() {
+    let attrs = NlaBuilder::new()
+        .append(3, "dummy0")
+        .append(4, 1500u32)
+        .nested(18, NlaBuilder::new().append(1, "dummy"))
+        .append(1, MacAddr::new(0, 1, 2, 3, 4, 5))
+        .build();
+    assert_eq!(attrs.len(), 12 + 8 + 16 + 12);
+    let mut iter = NlaIterator::new(&attrs);
+    assert_eq!(iter.next().unwrap().get::<String>().unwrap(), "dummy0");
+    assert_eq!(iter.next().unwrap().get::<u32>().unwrap(), 1500);
+    let linkinfo = iter.next().unwrap();
+    assert!(linkinfo.is_nested() && linkinfo.kind() == 18);
+    assert_eq!(linkinfo.nested().get::<String>(1).unwrap(), Some("dummy".to_owned()));
+    assert_eq!(iter.next().unwrap().get::<MacAddr>().unwrap(), MacAddr::new(0, 1, 2, 3, 4, 5));
+    assert!(iter.next().is_none() && !iter.is_truncated());
+    assert!(NlaIterator::new(&attrs).get::<u16>(4).is_err());
+}
+
+#[test]
+#[should_panic(expected = "netlink attribute 1 is 65540 bytes long")]
+fn too_long() {
+    let data = vec![0u8; 65536];
+    NlaBuilder::new().append(1, &data[..]);
+}
+
+#[test]
+fn truncated() {
+    let attrs = NlaBuilder::new().append(3, "dummy0").build();
+    let mut iter = NlaIterator::new(&attrs[..6]);
+    assert!(iter.next().is_none() && iter.is_truncated());
+    assert!(NlaIterator::new(&[255, 255, 1, 0]).next().is_none());
+}
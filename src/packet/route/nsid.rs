@@ -0,0 +1,145 @@
+//! Network namespace ids
+//!
+//! The kernel refers to peer namespaces by ids local to the namespace
+//! of the socket, e.g. in `IFLA_LINK_NETNSID` reported for veth peers.
+use packet::route::{MutableRtGenMsgPacket,RtGenMsgPacket};
+use packet::route::nla::{NlaIterator,emit_nla};
+use packet::netlink::{NetlinkPacket,NetlinkRequestBuilder,NetlinkConnection};
+use packet::netlink::{NetlinkMsgFlags,NLM_F_ACK,NLM_F_REQUEST};
+use netns::{self,NetNs};
+use error::{Error,Result};
+use libc;
+use pnet::packet::Packet;
+use std::fs::File;
+use std::os::unix::io::AsRawFd;
+
+pub const RTM_NEWNSID: u16 = 88;
+pub const RTM_DELNSID: u16 = 89;
+pub const RTM_GETNSID: u16 = 90;
+
+pub const NETNSA_NONE: u16 = 0;
+pub const NETNSA_NSID: u16 = 1;
+pub const NETNSA_PID: u16 = 2;
+pub const NETNSA_FD: u16 = 3;
+
+/// Id value meaning "no id assigned", or "let the kernel pick one"
+/// when assigning
+pub const NETNSA_NSID_NOT_ASSIGNED: i32 = -1;
+
+/// Namespace id operations trait
+pub trait NetnsIds {
+    /// Returns id of `netns`, `None` if no id is assigned
+    fn get_netns_id(&mut self, netns: &NetNs) -> Result<Option<i32>>;
+    /// Assigns id `id` to `netns`, `NETNSA_NSID_NOT_ASSIGNED`
+    /// lets the kernel pick a free one
+    fn set_netns_id(&mut self, netns: &NetNs, id: i32) -> Result<()>;
+    /// Resolves id `id` to a named namespace, see `netns::list`
+    fn get_netns_name(&mut self, id: i32) -> Result<Option<String>>;
+}
+
+impl NetnsIds for NetlinkConnection {
+    fn get_netns_id(&mut self, netns: &NetNs) -> Result<Option<i32>> {
+        let (req, _file) = try!(nsid_request(RTM_GETNSID, NLM_F_REQUEST, netns, None));
+        let mut reader = try!(self.send(req));
+        let pkt = match try!(reader.read_netlink()) {
+            Some(pkt) => pkt,
+            None => return Err(Error::Truncated),
+        };
+        if pkt.get_kind() != RTM_NEWNSID {
+            return Err(Error::Malformed("not a nsid message"));
+        }
+        let id = match RtGenMsgPacket::new(pkt.payload()) {
+            Some(msg) => try!(NlaIterator::new(msg.payload()).get::<i32>(NETNSA_NSID)),
+            None => return Err(Error::Truncated),
+        };
+        match id {
+            Some(NETNSA_NSID_NOT_ASSIGNED) | None => Ok(None),
+            Some(id) => Ok(Some(id)),
+        }
+    }
+
+    fn set_netns_id(&mut self, netns: &NetNs, id: i32) -> Result<()> {
+        let (req, _file) = try!(nsid_request(RTM_NEWNSID, NLM_F_ACK, netns, Some(id)));
+        let reader = try!(self.send(req));
+        reader.read_to_end()
+    }
+
+    fn get_netns_name(&mut self, id: i32) -> Result<Option<String>> {
+        for name in try!(netns::list()) {
+            match self.get_netns_id(&NetNs::named(&name)) {
+                Ok(Some(nsid)) if nsid == id => return Ok(Some(name)),
+                Ok(_) => {},
+                Err(ref e) if is_stale(e) => {},
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Whether `err` comes from a namespace entry removed while listing (ENOENT)
+/// or a file which is not a namespace, e.g. a bind mount gone (EINVAL)
+fn is_stale(err: &Error) -> bool {
+    match *err {
+        Error::Io(ref e) => e.raw_os_error() == Some(libc::ENOENT),
+        Error::Kernel(ref e) => e.errno() == libc::EINVAL,
+        _ => false,
+    }
+}
+
+/// Builds a request referring to `netns`, the returned file
+/// must stay open until the request is sent
+fn nsid_request(kind: u16, flags: NetlinkMsgFlags, netns: &NetNs, id: Option<i32>) -> Result<(NetlinkPacket<'static>, Option<File>)> {
+    let mut data = vec![0; MutableRtGenMsgPacket::minimum_packet_size()];
+    let file = match *netns {
+        NetNs::Pid(pid) => {
+            emit_nla(&mut data, NETNSA_PID, &pid);
+            None
+        },
+        NetNs::Fd(fd) => {
+            emit_nla(&mut data, NETNSA_FD, &(fd as u32));
+            None
+        },
+        NetNs::Path(_) => {
+            let file = try!(netns.open());
+            if let Some(ref file) = file {
+                emit_nla(&mut data, NETNSA_FD, &(file.as_raw_fd() as u32));
+            }
+            file
+        },
+    };
+    if let Some(id) = id {
+        emit_nla(&mut data, NETNSA_NSID, &id);
+    }
+    let req = NetlinkRequestBuilder::new(kind, flags)
+        .append(RtGenMsgPacket::owned(data).unwrap())
+        .build();
+    Ok((req, file))
+}
+
+#[test]
+fn nsid_request_attrs() {
+    use packet::netlink::NLM_F_REQUEST;
+
+    let (req, file) = nsid_request(RTM_NEWNSID, NLM_F_ACK, &NetNs::Pid(42), Some(3)).unwrap();
+    assert!(file.is_none());
+    assert_eq!(req.get_kind(), RTM_NEWNSID);
+    assert_eq!(req.get_flags(), NLM_F_REQUEST | NLM_F_ACK);
+    let msg = RtGenMsgPacket::new(req.payload()).unwrap();
+    let attrs = NlaIterator::new(msg.payload());
+    assert_eq!(attrs.clone().get::<u32>(NETNSA_PID).unwrap(), Some(42));
+    assert_eq!(attrs.clone().get::<i32>(NETNSA_NSID).unwrap(), Some(3));
+    assert_eq!(attrs.get::<u32>(NETNSA_FD).unwrap(), None);
+
+    let (req, _) = nsid_request(RTM_GETNSID, NLM_F_REQUEST, &NetNs::Fd(7), None).unwrap();
+    let msg = RtGenMsgPacket::new(req.payload()).unwrap();
+    let attrs = NlaIterator::new(msg.payload());
+    assert_eq!(attrs.clone().get::<u32>(NETNSA_FD).unwrap(), Some(7));
+    assert_eq!(attrs.get::<i32>(NETNSA_NSID).unwrap(), None);
+
+    // the namespace file is held open for the request
+    let (req, file) = nsid_request(RTM_GETNSID, NLM_F_REQUEST, &NetNs::Path("/proc/self/ns/net".into()), None).unwrap();
+    let fd = file.unwrap().as_raw_fd() as u32;
+    let msg = RtGenMsgPacket::new(req.payload()).unwrap();
+    assert_eq!(NlaIterator::new(msg.payload()).get::<u32>(NETNSA_FD).unwrap(), Some(fd));
+}
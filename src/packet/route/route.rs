@@ -1,12 +1,15 @@
 //! Route operations
 use packet::route::{RouteCacheInfoPacket,RtMsgPacket,MutableRtMsgPacket,MutableIfInfoPacket,RtAttrIterator,RtAttrPacket,MutableRtAttrPacket};
 use packet::route::link::Link;
+use packet::route::addr::{Addr,IpAddr,Scope};
+use packet::route::nla::{Nla,FromNla,NlaIterator,emit_nla};
 use packet::netlink::{MutableNetlinkPacket,NetlinkPacket,NetlinkErrorPacket};
 use packet::netlink::{NLM_F_ACK,NLM_F_REQUEST,NLM_F_DUMP,NLM_F_MATCH,NLM_F_EXCL,NLM_F_CREATE};
 use packet::netlink::{NLMSG_NOOP,NLMSG_ERROR,NLMSG_DONE,NLMSG_OVERRUN};
 use packet::netlink::{NetlinkBufIterator,NetlinkReader,NetlinkRequestBuilder};
 use socket::{NetlinkSocket,NetlinkProtocol};
-use packet::netlink::NetlinkConnection;
+use packet::netlink::{NetlinkConnection,DumpPolicy,message_bytes};
+use error::{Error,Result};
 use pnet::packet::MutablePacket;
 use pnet::packet::Packet;
 use pnet::packet::PacketSize;
@@ -47,7 +50,7 @@ enum RtmType {
 }
 
 bitflags! {
-    pub flags RtmFlags: u8 {
+    pub flags RtmFlags: u32 {
         const NOTIFY = 0x100,
         const CLONED = 0x200,
         const EQUALIZE = 0x400,
@@ -56,7 +59,7 @@ bitflags! {
 }
 
 impl RtmFlags {
-    pub fn new(val: u8) -> Self {
+    pub fn new(val: u32) -> Self {
         RtmFlags::from_bits_truncate(val)
     }
 }
@@ -84,18 +87,201 @@ pub struct Route {
     packet: NetlinkPacket<'static>,
 }
 
+/// Route borrowed from a receive buffer, see `Route::dump_routes`
+#[derive(Clone,Copy)]
+pub struct RouteView<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> RouteView<'a> {
+    /// Parses RTM_NEWROUTE or RTM_DELROUTE message
+    pub fn from_bytes(data: &'a [u8]) -> Result<Self> {
+        let (kind, len) = match NetlinkPacket::new(data) {
+            Some(pkt) => (pkt.get_kind(), pkt.get_length() as usize),
+            None => return Err(Error::Truncated),
+        };
+        if kind != RTM_NEWROUTE && kind != RTM_DELROUTE {
+            return Err(Error::Malformed("not a route message"));
+        }
+        let data = &data[..::std::cmp::min(len, data.len())];
+        if data.len() < NetlinkPacket::minimum_packet_size() + RtMsgPacket::minimum_packet_size() {
+            return Err(Error::Truncated);
+        }
+        Ok(RouteView { data: data })
+    }
+
+    /// Copies the message into an owned `Route`
+    pub fn to_route(&self) -> Route {
+        Route { packet: NetlinkPacket::owned(self.data.to_owned()).unwrap() }
+    }
+
+    /// Address family of the route
+    pub fn get_family(&self) -> u8 {
+        self.rtm().get_rtm_family()
+    }
+
+    /// Destination prefix length
+    pub fn get_dst_len(&self) -> u8 {
+        self.rtm().get_rtm_dst_len()
+    }
+
+    /// Source prefix length
+    pub fn get_src_len(&self) -> u8 {
+        self.rtm().get_rtm_src_len()
+    }
+
+    /// Type of service
+    pub fn get_tos(&self) -> u8 {
+        self.rtm().get_rtm_tos()
+    }
+
+    /// Routing table id, taken from RTA_TABLE when present
+    pub fn get_table(&self) -> Result<u32> {
+        match try!(self.get_nla(RTA_TABLE)) {
+            Some(table) => Ok(table),
+            None => Ok(self.rtm().get_rtm_table() as u32),
+        }
+    }
+
+    /// Routing protocol which installed the route
+    pub fn get_protocol(&self) -> u8 {
+        self.rtm().get_rtm_protocol()
+    }
+
+    /// Route scope
+    pub fn get_scope(&self) -> Scope {
+        self.rtm().get_rtm_scope()
+    }
+
+    /// Route type (unicast, local, blackhole, ...)
+    pub fn get_type(&self) -> u8 {
+        self.rtm().get_rtm_type()
+    }
+
+    /// Route flags
+    pub fn get_flags(&self) -> u32 {
+        self.rtm().get_rtm_flags()
+    }
+
+    /// Destination prefix, `None` for the default route
+    pub fn get_destination(&self) -> Result<Option<IpAddr>> {
+        self.get_ip_rta(RTA_DST)
+    }
+
+    /// Source prefix
+    pub fn get_source(&self) -> Result<Option<IpAddr>> {
+        self.get_ip_rta(RTA_SRC)
+    }
+
+    /// Gateway address
+    pub fn get_gateway(&self) -> Result<Option<IpAddr>> {
+        self.get_ip_rta(RTA_GATEWAY)
+    }
+
+    /// Preferred source address
+    pub fn get_prefsrc(&self) -> Result<Option<IpAddr>> {
+        self.get_ip_rta(RTA_PREFSRC)
+    }
+
+    /// Output interface index
+    pub fn get_oif(&self) -> Result<Option<u32>> {
+        self.get_nla(RTA_OIF)
+    }
+
+    /// Input interface index
+    pub fn get_iif(&self) -> Result<Option<u32>> {
+        self.get_nla(RTA_IIF)
+    }
+
+    /// Route priority (metric)
+    pub fn get_priority(&self) -> Result<Option<u32>> {
+        self.get_nla(RTA_PRIORITY)
+    }
+
+    /// Decodes attribute `kind`, `None` if it is missing
+    pub fn get_nla<T: FromNla>(&self, kind: u16) -> Result<Option<T>> {
+        self.attrs().get(kind)
+    }
+
+    /// Iterates over route attributes
+    pub fn attrs(&self) -> NlaIterator<'a> {
+        let offset = NetlinkPacket::minimum_packet_size() + RtMsgPacket::minimum_packet_size();
+        NlaIterator::new(&self.data[offset..])
+    }
+
+    fn get_ip_rta(&self, kind: u16) -> Result<Option<IpAddr>> {
+        match self.attrs().find_kind(kind) {
+            Some(nla) => Addr::ip_from_family_and_bytes(self.get_family(), nla.value()).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn rtm(&self) -> RtMsgPacket<'a> {
+        RtMsgPacket::new(&self.data[NetlinkPacket::minimum_packet_size()..]).unwrap()
+    }
+}
+
+impl<'a> ::std::fmt::Debug for RouteView<'a> {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "{:?}/{} table {:?} via {:?} dev {:?}",
+               self.get_destination().unwrap_or(None), self.get_dst_len(),
+               self.get_table().ok(), self.get_gateway().unwrap_or(None),
+               self.get_oif().unwrap_or(None))
+    }
+}
+
+/// Lending iterator over a routes dump, see `Route::dump_routes`
+pub struct RouteDump<R: Read> {
+    reader: NetlinkReader<R>,
+}
+
+impl<R: Read> RouteDump<R> {
+    /// Returns next route, it borrows the receive buffer
+    /// until the next call
+    pub fn next_route(&mut self) -> Result<Option<RouteView>> {
+        match try!(self.reader.next_message()) {
+            Some(data) => {
+                if NetlinkPacket::new(data).unwrap().get_kind() != RTM_NEWROUTE {
+                    return Ok(None);
+                }
+                RouteView::from_bytes(data).map(Some)
+            },
+            None => Ok(None),
+        }
+    }
+}
+
 impl Route {
+    /// Wraps RTM_NEWROUTE or RTM_DELROUTE message
+    pub fn from_packet(packet: NetlinkPacket<'static>) -> Result<Self> {
+        try!(RouteView::from_bytes(packet.packet()));
+        Ok(Route { packet: packet })
+    }
+
+    /// Borrows the route as a view
+    pub fn view(&self) -> RouteView {
+        RouteView { data: message_bytes(&self.packet) }
+    }
+
+    /// Returns a consistent snapshot of all routes, see `DumpPolicy`
+    pub fn snapshot_routes(conn: &mut NetlinkConnection, policy: DumpPolicy) -> Result<Vec<Route>> {
+        let pkts = try!(conn.dump(&dump_routes_request(), policy));
+        pkts.into_iter()
+            .filter(|pkt| pkt.get_kind() == RTM_NEWROUTE)
+            .map(Route::from_packet)
+            .collect()
+    }
+
+    /// Iterate over routes without copying them out of the receive buffer
+    pub fn dump_routes(conn: &mut NetlinkConnection) -> Result<RouteDump<&mut NetlinkConnection>> {
+        let reply = try!(conn.send(dump_routes_request()));
+        Ok(RouteDump { reader: reply })
+    }
+
     /// Iterate over routes
-    pub fn iter_routes(conn: &mut NetlinkConnection) -> RoutesIterator<&mut NetlinkConnection> {
-        let mut buf = vec![0; MutableIfInfoPacket::minimum_packet_size()];
-        let req = NetlinkRequestBuilder::new(RTM_GETROUTE, NLM_F_DUMP)
-            .append({
-                let mut ifinfo = MutableIfInfoPacket::new(&mut buf).unwrap();
-                ifinfo.set_family(0 /* AF_UNSPEC */);
-                ifinfo
-            }).build();
-        let mut reply = conn.send(req);
-        RoutesIterator { iter: reply.into_iter() }
+    pub fn iter_routes(conn: &mut NetlinkConnection) -> Result<RoutesIterator<&mut NetlinkConnection>> {
+        let reply = try!(conn.send(dump_routes_request()));
+        Ok(RoutesIterator { iter: reply.into_iter() })
     }
 
     fn dump_route(msg: NetlinkPacket) {
@@ -159,26 +345,75 @@ pub struct RoutesIterator<R: Read> {
 }
 
 impl<R: Read> Iterator for RoutesIterator<R> {
-    type Item = Route;
+    type Item = Result<Route>;
 
     fn next(&mut self) -> Option<Self::Item> {
         match self.iter.next() {
-            Some(pkt) => {
+            Some(Ok(pkt)) => {
                 let kind = pkt.get_kind();
                 if kind != RTM_NEWROUTE {
                     return None;
                 }
-                return Some(Route { packet: pkt });
+                return Some(Route::from_packet(pkt));
             },
+            Some(Err(e)) => Some(Err(e)),
             None => None,
         }
     }
 }
 
+fn dump_routes_request() -> NetlinkPacket<'static> {
+    NetlinkRequestBuilder::new(RTM_GETROUTE, NLM_F_DUMP)
+        .append(
+            RtMsgRequestBuilder::new()
+                .with_rtm(|mut rtm| rtm.set_rtm_family(0 /* AF_UNSPEC */))
+                .build()
+        ).build()
+}
+
+struct RtMsgRequestBuilder {
+    data: Vec<u8>,
+}
+
+impl RtMsgRequestBuilder {
+    pub fn new() -> Self {
+        let data = vec![0; MutableRtMsgPacket::minimum_packet_size()];
+        RtMsgRequestBuilder { data: data }
+    }
+
+    pub fn with_rtm<F>(mut self, f: F) -> Self
+        where F: Fn(MutableRtMsgPacket) -> () {
+        {
+            let pkt = MutableRtMsgPacket::new(&mut self.data[..]).unwrap();
+            f(pkt);
+        }
+        self
+    }
+
+    /// Appends attribute `kind` with value `value`
+    pub fn nla<T: Nla>(mut self, kind: u16, value: T) -> Self {
+        emit_nla(&mut self.data, kind, &value);
+        self
+    }
+
+    pub fn build(self) -> RtMsgPacket<'static> {
+        RtMsgPacket::owned(self.data).unwrap()
+    }
+}
+
 #[test]
 fn dump_routes() {
-    let mut conn = NetlinkConnection::new();
-    for route in Route::iter_routes(&mut conn) {
-        Route::dump_route(route.packet.get_packet());
+    let mut conn = NetlinkConnection::new().unwrap();
+    for route in Route::iter_routes(&mut conn).unwrap() {
+        Route::dump_route(route.unwrap().packet);
+    }
+}
+
+#[test]
+fn dump_route_views() {
+    let mut conn = NetlinkConnection::new().unwrap();
+    let mut dump = Route::dump_routes(&mut conn).unwrap();
+    while let Some(route) = dump.next_route().unwrap() {
+        assert_eq!(route.to_route().view().get_table().unwrap(), route.get_table().unwrap());
     }
 }
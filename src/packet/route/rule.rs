@@ -6,7 +6,8 @@ use packet::netlink::{NLM_F_ACK,NLM_F_REQUEST,NLM_F_DUMP,NLM_F_MATCH,NLM_F_EXCL,
 use packet::netlink::{NLMSG_NOOP,NLMSG_ERROR,NLMSG_DONE,NLMSG_OVERRUN};
 use packet::netlink::{NetlinkBufIterator,NetlinkReader,NetlinkRequestBuilder};
 use socket::{NetlinkSocket,NetlinkProtocol};
-use packet::netlink::NetlinkConnection;
+use packet::netlink::{NetlinkConnection,DumpPolicy};
+use error::{Error,Result};
 use pnet::packet::MutablePacket;
 use pnet::packet::Packet;
 use pnet::packet::PacketSize;
@@ -46,17 +47,31 @@ pub struct Rule {
 }
 
 impl Rule {
+    /// Wraps RTM_NEWRULE or RTM_DELRULE message
+    pub fn from_packet(packet: NetlinkPacket<'static>) -> Result<Self> {
+        let kind = packet.get_kind();
+        if kind != RTM_NEWRULE && kind != RTM_DELRULE {
+            return Err(Error::Malformed("not a rule message"));
+        }
+        if FibRulePacket::new(packet.payload()).is_none() {
+            return Err(Error::Truncated);
+        }
+        Ok(Rule { packet: packet })
+    }
+
     /// iterate over rules
-    pub fn iter_rules(conn: &mut NetlinkConnection) -> RulesIterator<&mut NetlinkConnection> {
-        let mut buf = vec![0; MutableIfInfoPacket::minimum_packet_size()];
-        let req = NetlinkRequestBuilder::new(RTM_GETRULE, NLM_F_DUMP)
-            .append({
-                let mut ifinfo = MutableIfInfoPacket::new(&mut buf).unwrap();
-                ifinfo.set_family(0 /* AF_UNSPEC */);
-                ifinfo
-            }).build();
-        let mut reply = conn.send(req);
-        RulesIterator { iter: reply.into_iter() }
+    pub fn iter_rules(conn: &mut NetlinkConnection) -> Result<RulesIterator<&mut NetlinkConnection>> {
+        let reply = try!(conn.send(dump_rules_request()));
+        Ok(RulesIterator { iter: reply.into_iter() })
+    }
+
+    /// Returns a consistent snapshot of all rules, see `DumpPolicy`
+    pub fn snapshot_rules(conn: &mut NetlinkConnection, policy: DumpPolicy) -> Result<Vec<Rule>> {
+        let pkts = try!(conn.dump(&dump_rules_request(), policy));
+        pkts.into_iter()
+            .filter(|pkt| pkt.get_kind() == RTM_NEWRULE)
+            .map(Rule::from_packet)
+            .collect()
     }
 
     fn dump_rule(msg: NetlinkPacket) {
@@ -91,22 +106,33 @@ impl Rule {
     }
 }
 
+fn dump_rules_request() -> NetlinkPacket<'static> {
+    let mut buf = vec![0; MutableIfInfoPacket::minimum_packet_size()];
+    NetlinkRequestBuilder::new(RTM_GETRULE, NLM_F_DUMP)
+        .append({
+            let mut ifinfo = MutableIfInfoPacket::new(&mut buf).unwrap();
+            ifinfo.set_family(0 /* AF_UNSPEC */);
+            ifinfo
+        }).build()
+}
+
 pub struct RulesIterator<R: Read> {
     iter: NetlinkBufIterator<R>,
 }
 
 impl<R: Read> Iterator for RulesIterator<R> {
-    type Item = Rule;
+    type Item = Result<Rule>;
 
     fn next(&mut self) -> Option<Self::Item> {
         match self.iter.next() {
-            Some(pkt) => {
+            Some(Ok(pkt)) => {
                 let kind = pkt.get_kind();
                 if kind != RTM_NEWRULE {
                     return None;
                 }
-                return Some(Rule { packet: pkt });
+                return Some(Rule::from_packet(pkt));
             },
+            Some(Err(e)) => Some(Err(e)),
             None => None,
         }
     }
@@ -114,8 +140,8 @@ impl<R: Read> Iterator for RulesIterator<R> {
 
 #[test]
 fn dump_rules() {
-    let mut conn = NetlinkConnection::new();
-    for rule in Route::iter_rules(&mut conn) {
-        Route::dump_rule(rule.packet.get_packet());
+    let mut conn = NetlinkConnection::new().unwrap();
+    for rule in Rule::iter_rules(&mut conn).unwrap() {
+        Rule::dump_rule(rule.unwrap().packet);
     }
 }
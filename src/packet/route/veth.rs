@@ -0,0 +1,137 @@
+//! Virtual ethernet pairs, see `Links::create_veth`
+use packet::route::MutableIfInfoPacket;
+use packet::route::link::{LinkSpec,LinkType,IFLA_IFNAME,IFLA_ADDRESS,IFLA_MTU,IFLA_NET_NS_FD,IFLA_NET_NS_PID};
+use packet::route::nla::NlaBuilder;
+use netns::NetNs;
+use error::Result;
+use pnet::util::MacAddr;
+use std::fs::File;
+use std::os::unix::io::AsRawFd;
+
+pub const VETH_INFO_UNSPEC: u16 = 0;
+pub const VETH_INFO_PEER: u16 = 1;
+
+/// Veth pair to create, the peer may be placed into another
+/// network namespace in the same request
+pub struct VethSpec {
+    link: LinkSpec,
+    peer_name: String,
+    peer_attrs: NlaBuilder,
+    peer_netns: Option<NetNs>,
+}
+
+impl VethSpec {
+    /// Pair of links `name` and `peer_name`
+    pub fn new(name: &str, peer_name: &str) -> Self {
+        VethSpec {
+            link: LinkSpec::new(name, LinkType::Veth),
+            peer_name: peer_name.to_owned(),
+            peer_attrs: NlaBuilder::new(),
+            peer_netns: None,
+        }
+    }
+
+    /// Name of the local end
+    pub fn get_name(&self) -> &str {
+        self.link.get_name()
+    }
+
+    /// Name of the peer
+    pub fn get_peer_name(&self) -> &str {
+        &self.peer_name
+    }
+
+    /// Namespace the peer is placed into, `None` for the local one
+    pub fn get_peer_netns(&self) -> Option<&NetNs> {
+        self.peer_netns.as_ref()
+    }
+
+    /// Brings the local end up
+    pub fn up(mut self) -> Self {
+        self.link = self.link.up();
+        self
+    }
+
+    pub fn mtu(mut self, mtu: u32) -> Self {
+        self.link = self.link.mtu(mtu);
+        self
+    }
+
+    pub fn hw_addr(mut self, addr: MacAddr) -> Self {
+        self.link = self.link.hw_addr(addr);
+        self
+    }
+
+    pub fn peer_mtu(mut self, mtu: u32) -> Self {
+        self.peer_attrs = self.peer_attrs.append(IFLA_MTU, mtu);
+        self
+    }
+
+    pub fn peer_hw_addr(mut self, addr: MacAddr) -> Self {
+        self.peer_attrs = self.peer_attrs.append(IFLA_ADDRESS, addr);
+        self
+    }
+
+    /// Creates the peer inside `netns`
+    pub fn peer_netns(mut self, netns: NetNs) -> Self {
+        self.peer_netns = Some(netns);
+        self
+    }
+
+    /// Converts into a `LinkSpec`, the returned file holds the peer
+    /// namespace open and must live until the request is sent
+    pub fn into_link_spec(self) -> Result<(LinkSpec, Option<File>)> {
+        let mut peer = NlaBuilder::new().append(IFLA_IFNAME, &self.peer_name[..]);
+        let mut file = None;
+        peer = match self.peer_netns {
+            Some(NetNs::Pid(pid)) => peer.append(IFLA_NET_NS_PID, pid),
+            Some(NetNs::Fd(fd)) => peer.append(IFLA_NET_NS_FD, fd as u32),
+            Some(ref netns @ NetNs::Path(_)) => {
+                file = try!(netns.open());
+                match file {
+                    Some(ref file) => peer.append(IFLA_NET_NS_FD, file.as_raw_fd() as u32),
+                    None => peer,
+                }
+            },
+            None => peer,
+        };
+        // VETH_INFO_PEER carries a complete ifinfomsg followed by attributes,
+        // the peer can't be brought up here as it is not paired yet
+        let mut ifinfo = vec![0; MutableIfInfoPacket::minimum_packet_size()];
+        ifinfo.extend_from_slice(&peer.build());
+        ifinfo.extend_from_slice(&self.peer_attrs.build());
+        let spec = self.link.info_data(NlaBuilder::new().append(VETH_INFO_PEER, ifinfo));
+        Ok((spec, file))
+    }
+}
+
+#[test]
+fn veth_peer_info() {
+    use packet::route::nla::NlaIterator;
+
+    let peer_info = |spec: VethSpec| {
+        let (spec, file) = spec.into_link_spec().unwrap();
+        assert!(file.is_none());
+        let data = spec.get_info_data().unwrap().clone().build();
+        let peer = NlaIterator::new(&data).find_kind(VETH_INFO_PEER).unwrap().value().to_owned();
+        // ifinfomsg of the peer, left for the kernel to fill in
+        let ifinfo_len = MutableIfInfoPacket::minimum_packet_size();
+        assert_eq!(ifinfo_len, 16);
+        assert_eq!(&peer[..ifinfo_len], &[0; 16][..]);
+        peer[ifinfo_len..].to_owned()
+    };
+
+    let attrs = peer_info(VethSpec::new("veth0", "veth1").peer_mtu(9000).peer_netns(NetNs::Pid(42)));
+    let attrs = NlaIterator::new(&attrs);
+    assert_eq!(attrs.clone().get_str(IFLA_IFNAME).unwrap(), Some("veth1"));
+    assert_eq!(attrs.clone().get::<u32>(IFLA_NET_NS_PID).unwrap(), Some(42));
+    assert_eq!(attrs.clone().get::<u32>(IFLA_NET_NS_FD).unwrap(), None);
+    assert_eq!(attrs.get::<u32>(IFLA_MTU).unwrap(), Some(9000));
+
+    let mac = MacAddr::new(2, 0, 0, 0, 0, 1);
+    let attrs = peer_info(VethSpec::new("veth0", "veth1").peer_hw_addr(mac).peer_netns(NetNs::Fd(7)));
+    let attrs = NlaIterator::new(&attrs);
+    assert_eq!(attrs.clone().get::<u32>(IFLA_NET_NS_FD).unwrap(), Some(7));
+    assert_eq!(attrs.clone().get::<u32>(IFLA_NET_NS_PID).unwrap(), None);
+    assert_eq!(attrs.get::<MacAddr>(IFLA_ADDRESS).unwrap(), Some(mac));
+}
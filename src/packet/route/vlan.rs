@@ -0,0 +1,295 @@
+//! 802.1Q and 802.1ad VLAN links
+//!
+//! # Example
+//! ```ignore
+//! let parent = conn.get_link_by_name("eth0")?.unwrap();
+//! let spec = VlanSpec::new("eth0.100", &parent, 100)
+//!     .egress_qos(0, 3)
+//!     .up();
+//! let link = conn.create_link(spec.into_link_spec())?;
+//! println!("{:?}", VlanInfo::from_link(&link)?);
+//! ```
+use packet::route::link::{Link,LinkSpec,LinkType};
+use packet::route::nla::{NlaBuilder,NlaIterator};
+use error::{Error,Result};
+use byteorder::{ByteOrder,BigEndian,NativeEndian};
+use pnet::util::MacAddr;
+
+pub const IFLA_VLAN_UNSPEC: u16 = 0;
+pub const IFLA_VLAN_ID: u16 = 1;
+pub const IFLA_VLAN_FLAGS: u16 = 2;
+pub const IFLA_VLAN_EGRESS_QOS: u16 = 3;
+pub const IFLA_VLAN_INGRESS_QOS: u16 = 4;
+pub const IFLA_VLAN_PROTOCOL: u16 = 5;
+
+pub const IFLA_VLAN_QOS_UNSPEC: u16 = 0;
+pub const IFLA_VLAN_QOS_MAPPING: u16 = 1;
+
+pub const ETH_P_8021Q: u16 = 0x8100;
+pub const ETH_P_8021AD: u16 = 0x88a8;
+
+bitflags! {
+    pub flags VlanFlags: u32 {
+        /// strip the VLAN header on receive
+        const REORDER_HDR = 0x1,
+        /// register the VLAN with GVRP
+        const GVRP = 0x2,
+        /// operational state follows the parent only when up
+        const LOOSE_BINDING = 0x4,
+        /// register the VLAN with MVRP
+        const MVRP = 0x8,
+        /// operational state follows the bridge VLAN
+        const BRIDGE_BINDING = 0x10,
+    }
+}
+
+/// Tag protocol
+#[derive(Copy,Clone,Debug,PartialEq,Eq)]
+pub enum VlanProtocol {
+    /// 802.1Q, the default
+    Dot1Q,
+    /// 802.1ad, aka QinQ service tag
+    Dot1AD,
+}
+
+impl VlanProtocol {
+    pub fn from_ethertype(val: u16) -> Result<Self> {
+        match val {
+            ETH_P_8021Q => Ok(VlanProtocol::Dot1Q),
+            ETH_P_8021AD => Ok(VlanProtocol::Dot1AD),
+            _ => Err(Error::Malformed("unknown VLAN protocol")),
+        }
+    }
+
+    pub fn ethertype(&self) -> u16 {
+        match *self {
+            VlanProtocol::Dot1Q => ETH_P_8021Q,
+            VlanProtocol::Dot1AD => ETH_P_8021AD,
+        }
+    }
+}
+
+/// VLAN link to create on top of a parent link
+pub struct VlanSpec {
+    link: LinkSpec,
+    id: u16,
+    protocol: Option<VlanProtocol>,
+    flags: VlanFlags,
+    mask: VlanFlags,
+    egress_qos: Vec<(u32, u32)>,
+    ingress_qos: Vec<(u32, u32)>,
+}
+
+impl VlanSpec {
+    /// Link `name` tagging frames of `parent` with `id`
+    pub fn new(name: &str, parent: &Link, id: u16) -> Self {
+        VlanSpec {
+            link: LinkSpec::new(name, LinkType::Vlan).link(parent.get_index()),
+            id: id,
+            protocol: None,
+            flags: VlanFlags::empty(),
+            mask: VlanFlags::empty(),
+            egress_qos: vec![],
+            ingress_qos: vec![],
+        }
+    }
+
+    pub fn protocol(mut self, protocol: VlanProtocol) -> Self {
+        self.protocol = Some(protocol);
+        self
+    }
+
+    /// Sets or clears `flags`, others are left at the kernel's defaults
+    pub fn set_flags(mut self, flags: VlanFlags, enable: bool) -> Self {
+        self.mask.insert(flags);
+        if enable {
+            self.flags.insert(flags);
+        } else {
+            self.flags.remove(flags);
+        }
+        self
+    }
+
+    /// Maps skb priority `from` to VLAN priority `to` on transmit
+    pub fn egress_qos(mut self, from: u32, to: u32) -> Self {
+        self.egress_qos.push((from, to));
+        self
+    }
+
+    /// Maps VLAN priority `from` to skb priority `to` on receive
+    pub fn ingress_qos(mut self, from: u32, to: u32) -> Self {
+        self.ingress_qos.push((from, to));
+        self
+    }
+
+    /// Brings the link up once created
+    pub fn up(mut self) -> Self {
+        self.link = self.link.up();
+        self
+    }
+
+    pub fn mtu(mut self, mtu: u32) -> Self {
+        self.link = self.link.mtu(mtu);
+        self
+    }
+
+    pub fn hw_addr(mut self, addr: MacAddr) -> Self {
+        self.link = self.link.hw_addr(addr);
+        self
+    }
+
+    /// Converts into a `LinkSpec` for `Links::create_link`
+    pub fn into_link_spec(self) -> LinkSpec {
+        let data = self.info_data();
+        self.link.info_data(data)
+    }
+
+    fn info_data(&self) -> NlaBuilder {
+        let mut data = NlaBuilder::new().append(IFLA_VLAN_ID, self.id);
+        if let Some(protocol) = self.protocol {
+            let mut buf = [0; 2];
+            BigEndian::write_u16(&mut buf, protocol.ethertype());
+            data = data.append(IFLA_VLAN_PROTOCOL, &buf[..]);
+        }
+        if !self.mask.is_empty() {
+            data = data.append(IFLA_VLAN_FLAGS, pair(self.flags.bits(), self.mask.bits()));
+        }
+        if !self.egress_qos.is_empty() {
+            data = data.nested(IFLA_VLAN_EGRESS_QOS, qos_map(&self.egress_qos));
+        }
+        if !self.ingress_qos.is_empty() {
+            data = data.nested(IFLA_VLAN_INGRESS_QOS, qos_map(&self.ingress_qos));
+        }
+        data
+    }
+}
+
+/// VLAN settings of an existing link
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct VlanInfo {
+    id: u16,
+    protocol: VlanProtocol,
+    flags: VlanFlags,
+    egress_qos: Vec<(u32, u32)>,
+    ingress_qos: Vec<(u32, u32)>,
+}
+
+impl VlanInfo {
+    /// Decodes VLAN settings, `None` if `link` is not a VLAN
+    pub fn from_link(link: &Link) -> Result<Option<Self>> {
+        if try!(link.view().get_kind()) != Some(LinkType::Vlan.kind()) {
+            return Ok(None);
+        }
+        match link.get_info_data() {
+            Some(data) => VlanInfo::from_info_data(data).map(Some),
+            None => Err(Error::Malformed("VLAN link without IFLA_INFO_DATA")),
+        }
+    }
+
+    /// Decodes attributes nested in IFLA_INFO_DATA
+    pub fn from_info_data(data: NlaIterator) -> Result<Self> {
+        let mut info = VlanInfo {
+            id: 0,
+            protocol: VlanProtocol::Dot1Q,
+            flags: VlanFlags::empty(),
+            egress_qos: vec![],
+            ingress_qos: vec![],
+        };
+        let mut has_id = false;
+        for nla in data {
+            match nla.kind() {
+                IFLA_VLAN_ID => {
+                    info.id = try!(nla.get());
+                    has_id = true;
+                },
+                IFLA_VLAN_PROTOCOL => {
+                    if nla.value().len() != 2 {
+                        return Err(Error::Malformed("attribute length"));
+                    }
+                    info.protocol = try!(VlanProtocol::from_ethertype(BigEndian::read_u16(nla.value())));
+                },
+                IFLA_VLAN_FLAGS => {
+                    let (flags, _mask) = try!(read_pair(nla.value()));
+                    info.flags = VlanFlags::from_bits_truncate(flags);
+                },
+                IFLA_VLAN_EGRESS_QOS => info.egress_qos = try!(read_qos_map(nla.nested())),
+                IFLA_VLAN_INGRESS_QOS => info.ingress_qos = try!(read_qos_map(nla.nested())),
+                _ => {},
+            }
+        }
+        if !has_id {
+            return Err(Error::Malformed("VLAN link without IFLA_VLAN_ID"));
+        }
+        Ok(info)
+    }
+
+    pub fn get_id(&self) -> u16 {
+        self.id
+    }
+
+    pub fn get_protocol(&self) -> VlanProtocol {
+        self.protocol
+    }
+
+    pub fn get_flags(&self) -> VlanFlags {
+        self.flags
+    }
+
+    /// skb priority to VLAN priority mappings
+    pub fn get_egress_qos(&self) -> &[(u32, u32)] {
+        &self.egress_qos
+    }
+
+    /// VLAN priority to skb priority mappings
+    pub fn get_ingress_qos(&self) -> &[(u32, u32)] {
+        &self.ingress_qos
+    }
+}
+
+/// Encodes two native endian u32, as in `ifla_vlan_flags`
+/// and `ifla_vlan_qos_mapping`
+fn pair(a: u32, b: u32) -> Vec<u8> {
+    let mut buf = vec![0; 8];
+    NativeEndian::write_u32(&mut buf[..4], a);
+    NativeEndian::write_u32(&mut buf[4..], b);
+    buf
+}
+
+fn read_pair(buf: &[u8]) -> Result<(u32, u32)> {
+    if buf.len() != 8 {
+        return Err(Error::Malformed("attribute length"));
+    }
+    Ok((NativeEndian::read_u32(&buf[..4]), NativeEndian::read_u32(&buf[4..])))
+}
+
+fn qos_map(map: &[(u32, u32)]) -> NlaBuilder {
+    map.iter().fold(NlaBuilder::new(), |data, &(from, to)| {
+        data.append(IFLA_VLAN_QOS_MAPPING, pair(from, to))
+    })
+}
+
+fn read_qos_map(data: NlaIterator) -> Result<Vec<(u32, u32)>> {
+    data.filter(|nla| nla.kind() == IFLA_VLAN_QOS_MAPPING)
+        .map(|nla| read_pair(nla.value()))
+        .collect()
+}
+
+#[test]
+fn vlan_info_roundtrip() {
+    let spec = VlanSpec {
+        link: LinkSpec::new("eth0.100", LinkType::Vlan),
+        id: 100,
+        protocol: Some(VlanProtocol::Dot1AD),
+        flags: GVRP,
+        mask: GVRP | REORDER_HDR,
+        egress_qos: vec![(1, 3), (2, 5)],
+        ingress_qos: vec![(4, 6)],
+    };
+    let data = spec.info_data().build();
+    let info = VlanInfo::from_info_data(NlaIterator::new(&data)).unwrap();
+    assert_eq!(info.get_id(), 100);
+    assert_eq!(info.get_protocol(), VlanProtocol::Dot1AD);
+    assert_eq!(info.get_flags(), GVRP);
+    assert_eq!(info.get_egress_qos(), &[(1, 3), (2, 5)][..]);
+    assert_eq!(info.get_ingress_qos(), &[(4, 6)][..]);
+}
@@ -3,9 +3,18 @@ extern crate libc;
 
 use libc::{c_int,c_void};
 use libc::{socket,bind,send,recvfrom,setsockopt,getsockopt};
+use libc::{MSG_PEEK,MSG_TRUNC};
 use std::os::unix::io::{AsRawFd,RawFd};
-use std::io::{Error,Result};
+use std::io::{Error,ErrorKind,Result};
 use std::io::Read;
+use netns::NetNs;
+
+#[cfg(feature = "mio")]
+use mio::{Interest,Registry,Token};
+#[cfg(feature = "mio")]
+use mio::event::Source;
+#[cfg(feature = "mio")]
+use mio::unix::SourceFd;
 
 mod ffi {
 	use libc::{c_int, sa_family_t, c_short};
@@ -16,6 +25,7 @@ mod ffi {
 	pub const SOL_NETLINK: c_int = 270;
 
 	pub const SO_RCVBUF: c_int = 8;
+	pub const SO_RCVBUFFORCE: c_int = 33;
 
 	#[repr(C)]
 	#[derive(Copy,Clone)]
@@ -35,6 +45,10 @@ pub enum SockOpt {
 	PktInfo = 3,
 	BroadcastError = 4,
 	NoEnobufs = 5,
+	/// do not echo the request payload in error replies
+	CapAck = 10,
+	/// extended ACK: error replies carry a message and attribute offset
+	ExtAck = 11,
 }
 
 
@@ -82,10 +96,13 @@ impl Drop for NetlinkSocket {
 }
 
 impl NetlinkSocket {
+	/// Opens a socket for `proto` subscribed to multicast `groups`.
+	///
+	/// The port id is assigned by the kernel, so any number of sockets
+	/// may be bound in the same process, see `port_id`.
 	pub fn bind(proto: NetlinkProtocol, groups: u32) -> Result<NetlinkSocket> {
 		use std::mem::size_of;
 		use std::mem::transmute;
-		use libc::getpid;
 
 		let mut res = unsafe {
 			socket(ffi::PF_NETLINK, ffi::SOCK_DGRAM, proto as i32)
@@ -97,7 +114,7 @@ impl NetlinkSocket {
 		let mut sockaddr = ffi::sockaddr_nl {
 			nl_family: ffi::PF_NETLINK as u16,
 			nl_pad: 0,
-			nl_pid: unsafe { getpid() } as u32,
+			nl_pid: 0, /* let the kernel pick a unique port id */
 			nl_groups: groups,
 		};
 		res = unsafe {
@@ -109,7 +126,65 @@ impl NetlinkSocket {
 		Ok(sock)
 	}
 
-	pub fn send(&mut self, buf: &[u8]) -> Result<usize> {
+	/// Same as `bind` but the socket is opened inside network namespace `netns`
+	///
+	/// The socket keeps operating in `netns` regardless of the namespace
+	/// of the thread using it.
+	pub fn bind_in_netns(proto: NetlinkProtocol, groups: u32, netns: &NetNs) -> Result<NetlinkSocket> {
+		netns.run(move || NetlinkSocket::bind(proto, groups))
+	}
+
+	/// Returns the port id the kernel assigned to this socket
+	pub fn port_id(&self) -> Result<u32> {
+		use std::mem::{size_of,transmute};
+		use libc::{getsockname,socklen_t};
+
+		let mut sockaddr = ffi::sockaddr_nl {
+			nl_family: 0,
+			nl_pad: 0,
+			nl_pid: 0,
+			nl_groups: 0,
+		};
+		let mut len = size_of::<ffi::sockaddr_nl>() as socklen_t;
+		let res = unsafe {
+			getsockname(self.fd, transmute(&mut sockaddr), &mut len)
+		};
+		if res < 0 {
+			return Err(Error::last_os_error());
+		}
+		Ok(sockaddr.nl_pid)
+	}
+
+	/// Switch the socket between blocking and non-blocking (`O_NONBLOCK`) mode.
+	///
+	/// In non-blocking mode `send` and `recv` return an error of kind
+	/// `WouldBlock` instead of waiting for the kernel.
+	pub fn set_nonblocking(&mut self, nonblocking: bool) -> Result<()> {
+		use libc::{fcntl,F_GETFL,F_SETFL,O_NONBLOCK};
+
+		let flags = unsafe { fcntl(self.fd, F_GETFL) };
+		if flags < 0 {
+			return Err(Error::last_os_error());
+		}
+		let flags = if nonblocking { flags | O_NONBLOCK } else { flags & !O_NONBLOCK };
+		if unsafe { fcntl(self.fd, F_SETFL, flags) } < 0 {
+			return Err(Error::last_os_error());
+		}
+		Ok(())
+	}
+
+	/// Returns `true` if the socket is in non-blocking mode
+	pub fn is_nonblocking(&self) -> Result<bool> {
+		use libc::{fcntl,F_GETFL,O_NONBLOCK};
+
+		let flags = unsafe { fcntl(self.fd, F_GETFL) };
+		if flags < 0 {
+			return Err(Error::last_os_error());
+		}
+		Ok(flags & O_NONBLOCK != 0)
+	}
+
+	pub fn send(&self, buf: &[u8]) -> Result<usize> {
 		use libc::c_void;
 		let len = buf.len();
 		let res = unsafe {
@@ -121,14 +196,47 @@ impl NetlinkSocket {
 		Ok(res as usize)
 	}
 
-	pub fn recv(&mut self, buf: &mut [u8]) -> Result<usize> {
+	/// Receives a single datagram
+	///
+	/// The datagram's size is peeked first, if it does not fit into `buf`
+	/// it is left queued and `InvalidData` error wrapping
+	/// `Error::DatagramTruncated` is returned, see `peek_len`.
+	///
+	/// If another reader of the socket swaps the datagram between peek
+	/// and receive, a bigger one may still be received truncated, then
+	/// `Error::DatagramLost` is returned instead.
+	pub fn recv(&self, buf: &mut [u8]) -> Result<usize> {
+		let len = try!(self.peek_len());
+		if len > buf.len() {
+			return Err(datagram_error(::error::Error::DatagramTruncated {
+				len: len,
+				capacity: buf.len(),
+			}));
+		}
+		let res = try!(self.recv_flags(buf, MSG_TRUNC));
+		if res > buf.len() {
+			// already taken off the socket, retrying won't bring it back
+			return Err(datagram_error(::error::Error::DatagramLost {
+				len: res,
+				capacity: buf.len(),
+			}));
+		}
+		Ok(res)
+	}
+
+	/// Returns size of the next queued datagram without receiving it
+	pub fn peek_len(&self) -> Result<usize> {
+		self.recv_flags(&mut [], MSG_PEEK | MSG_TRUNC)
+	}
+
+	fn recv_flags(&self, buf: &mut [u8], flags: c_int) -> Result<usize> {
 		use libc::c_void;
 		use std::ptr::null_mut;
 		use libc::sockaddr;
 
 		let len = buf.len();
 		let res = unsafe {
-			recvfrom(self.fd, buf.as_mut_ptr() as *mut c_void, len, 0, null_mut::<sockaddr>(), null_mut::<u32>())
+			recvfrom(self.fd, buf.as_mut_ptr() as *mut c_void, len, flags, null_mut::<sockaddr>(), null_mut::<u32>())
 		};
 		if res < 0 {
 			return Err(Error::last_os_error());
@@ -175,6 +283,12 @@ impl NetlinkSocket {
 		self.setsockopt_int(ffi::SOL_SOCKET, ffi::SO_RCVBUF, len)
 	}
 
+	/// Same as `setrcvbuf` but ignores the `rmem_max` limit,
+	/// requires CAP_NET_ADMIN
+	pub fn setrcvbuf_force(&mut self, len: c_int) -> Result<()> {
+		self.setsockopt_int(ffi::SOL_SOCKET, ffi::SO_RCVBUFFORCE, len)
+	}
+
 	pub fn getrcvbuf(&mut self) -> Result<u32> {
 		self.getsockopt_int(ffi::SOL_SOCKET, ffi::SO_RCVBUF)
 	}
@@ -182,6 +296,29 @@ impl NetlinkSocket {
 	pub fn getsockopt(&mut self, option: SockOpt, val: bool) -> Result<u32> {
 		self.getsockopt_int(ffi::SOL_NETLINK, option as c_int)
 	}
+
+	/// Subscribes to multicast group `group`, e.g. `RtnlGroup::Link`
+	///
+	/// Unlike the `groups` mask passed to `bind` this works for any
+	/// group number, not only the first 32.
+	pub fn join_group<G: Into<u32>>(&mut self, group: G) -> Result<()> {
+		self.setsockopt_int(ffi::SOL_NETLINK, SockOpt::AddMembership as c_int, group.into() as c_int)
+	}
+
+	/// Unsubscribes from multicast group `group`
+	pub fn leave_group<G: Into<u32>>(&mut self, group: G) -> Result<()> {
+		self.setsockopt_int(ffi::SOL_NETLINK, SockOpt::DropMembership as c_int, group.into() as c_int)
+	}
+
+	/// Enables extended ACKs and caps the request echoed in error replies
+	pub fn enable_ext_ack(&mut self) -> Result<()> {
+		try!(self.setsockopt(SockOpt::ExtAck, true));
+		self.setsockopt(SockOpt::CapAck, true)
+	}
+}
+
+fn datagram_error(err: ::error::Error) -> Error {
+	Error::new(ErrorKind::InvalidData, err)
 }
 
 impl Read for NetlinkSocket {
@@ -189,3 +326,34 @@ impl Read for NetlinkSocket {
 		self.recv(buf)
 	}
 }
+
+impl<'a> Read for &'a NetlinkSocket {
+	fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+		self.recv(buf)
+	}
+}
+
+#[cfg(feature = "mio")]
+impl Source for NetlinkSocket {
+	fn register(&mut self, registry: &Registry, token: Token, interests: Interest) -> Result<()> {
+		SourceFd(&self.fd).register(registry, token, interests)
+	}
+
+	fn reregister(&mut self, registry: &Registry, token: Token, interests: Interest) -> Result<()> {
+		SourceFd(&self.fd).reregister(registry, token, interests)
+	}
+
+	fn deregister(&mut self, registry: &Registry) -> Result<()> {
+		SourceFd(&self.fd).deregister(registry)
+	}
+}
+
+#[test]
+fn recv_would_block() {
+	let mut sock = NetlinkSocket::bind(NetlinkProtocol::Route, 0).unwrap();
+	sock.set_nonblocking(true).unwrap();
+	assert!(sock.is_nonblocking().unwrap());
+	let mut buf = [0u8; 64];
+	let err = sock.recv(&mut buf).unwrap_err();
+	assert_eq!(err.kind(), ErrorKind::WouldBlock);
+}